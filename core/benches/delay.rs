@@ -0,0 +1,120 @@
+//! The delay handler against the one it replaced, which kept pending delays in a binary heap.
+//!
+//! Run with `cargo bench -p voyagers_core`.
+
+use std::cell::Cell;
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+use criterion::{BatchSize, BenchmarkId, Criterion, criterion_group, criterion_main};
+use rand::{Rng, SeedableRng};
+use rand::rngs::StdRng;
+
+use voyagers_core::actors::{ActorBuilder, Envelope, System, SystemInterface};
+use voyagers_core::actors::Fate::Keep;
+use voyagers_core::clock::{FixedTimestep, ManualClock, Tick};
+use voyagers_core::delay::{delay_from_now, init_delay_handler};
+
+/// A scan ping on its way back.
+#[derive(Clone)]
+struct Ping;
+
+/// Delay a message in the old delay handler.
+struct HeapDelay(Duration, Rc<dyn Fn() -> Envelope>);
+
+/// The old delay handler: a heap by when delays are due, and the pending ones by handle.
+#[derive(Default)]
+struct HeapTimers {
+    by_time: BinaryHeap<Reverse<(Instant, u64)>>,
+    pending: HashMap<u64, Rc<dyn Fn() -> Envelope>>,
+    next_handle: u64,
+}
+
+fn init_heap_delay_handler(system: &mut System) {
+    system.create_actor(ActorBuilder::new(HeapTimers::default())
+        .with_handler(|st, HeapDelay(delay, envelope), outbox| {
+            st.by_time.push(Reverse((outbox.now() + *delay, st.next_handle)));
+            st.pending.insert(st.next_handle, envelope.clone());
+            st.next_handle += 1;
+            Keep
+        })
+        .with_handler(|st, _: &Tick, outbox| {
+            while let Some(Reverse((at, handle))) = st.by_time.peek().copied() {
+                if at >= outbox.now() {
+                    break;
+                }
+                st.by_time.pop();
+                if let Some(envelope) = st.pending.remove(&handle) {
+                    outbox.send_envelope(envelope());
+                }
+            }
+            Keep
+        })
+        .build());
+}
+
+/// A system on a ManualClock with the given delay handler, and a count of the Pings that came back.
+fn setup(init: fn(&mut System)) -> (System, ManualClock, Rc<Cell<usize>>) {
+    let mut system = System::new();
+
+    let clock = ManualClock::new();
+    system.set_clock(clock.clone());
+
+    init(&mut system);
+
+    let pings = Rc::new(Cell::new(0));
+    let counted = pings.clone();
+    system.create_actor(ActorBuilder::new(()).with_handler(move |_, _: &Ping, _| {
+        counted.set(counted.get() + 1);
+        Keep
+    }).build());
+
+    (system, clock, pings)
+}
+
+/// Delay a Ping by each of the given delays, then tick at 60 per second until all of them came back.
+fn run(system: &mut System, clock: &ManualClock, pings: &Cell<usize>, delays: &[Duration], delay: fn(&mut SystemInterface, Duration)) {
+    const FRAME: Duration = Duration::from_nanos(1_000_000_000 / 60);
+
+    for d in delays {
+        delay(&mut system.input_interface, *d);
+    }
+    while system.handle_one() {}
+
+    let mut timestep = FixedTimestep::new(FRAME);
+    while pings.get() < delays.len() {
+        clock.advance(FRAME);
+        system.send(timestep.step());
+        while system.handle_one() {}
+    }
+}
+
+fn delay_handlers(c: &mut Criterion) {
+    let mut group = c.benchmark_group("scan pings");
+    group.sample_size(10);
+
+    for count in [1_000, 10_000, 100_000] {
+        // Spread out over a scan timeout of 15 seconds.
+        let mut rng = StdRng::seed_from_u64(0);
+        let delays: Vec<Duration> = (0..count).map(|_| Duration::from_millis(rng.gen_range(0..15_000))).collect();
+
+        group.bench_with_input(BenchmarkId::new("binary heap", count), &delays, |b, delays| {
+            b.iter_batched(|| setup(init_heap_delay_handler), |(mut system, clock, pings)| {
+                run(&mut system, &clock, &pings, delays, |outbox, d| outbox.send(HeapDelay(d, Rc::new(|| Envelope::new(Ping)))));
+            }, BatchSize::LargeInput);
+        });
+
+        group.bench_with_input(BenchmarkId::new("timer wheel", count), &delays, |b, delays| {
+            b.iter_batched(|| setup(|system| init_delay_handler(system, 0)), |(mut system, clock, pings)| {
+                run(&mut system, &clock, &pings, delays, |outbox, d| { delay_from_now(outbox, Ping, d); });
+            }, BatchSize::LargeInput);
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, delay_handlers);
+criterion_main!(benches);
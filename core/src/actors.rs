@@ -0,0 +1,1392 @@
+use std::any::{Any, type_name, TypeId};
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::hash::Hash;
+use std::ops::{Deref, DerefMut};
+use std::panic::{self, AssertUnwindSafe};
+use std::rc::Rc;
+use std::thread;
+use std::time::Instant;
+
+use slotmap::new_key_type;
+use slotmap::{Key, SecondaryMap, SlotMap};
+
+use crate::actors::Fate::{Become, End, Keep};
+use crate::clock::{Clock, RealClock, Tick, Timeline};
+use crate::parallel::{SendActorData, SendActors, SendRun, SharedMessage};
+use crate::replay::{RecordedTypes, Recording, Session};
+use crate::trace::Trace;
+
+new_key_type! {
+    /// Internal type referring to an entry in the SlotMap of actor states.
+    pub struct StateKey;
+}
+new_key_type! { pub struct HandlerKey; }
+
+type Handler = dyn Fn(&mut dyn Any, &dyn Any, &mut SystemInterface) -> Fate;
+
+/// A message concerning one particular entity, such that it can be routed
+/// to only the actors that subscribed to that entity's key.
+pub trait Keyed: 'static {
+    type Key: Eq + Hash + Clone + 'static;
+
+    fn key(&self) -> &Self::Key;
+}
+
+/// Stable address of an actor, valid for as long as the actor is alive.
+#[derive(Clone, Copy, Eq, PartialEq, Hash, Debug)]
+pub struct Addr(pub(crate) StateKey);
+
+impl fmt::Display for Addr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}", self.0.data())
+    }
+}
+
+/// Reported when a handler panics: broadcast, or sent only to the parent of actors that escalate.
+#[derive(Clone, Debug)]
+pub struct ActorFailed {
+    pub actor: Addr,
+    pub message_type: &'static str,
+    pub payload: String,
+}
+
+/// A message that was not received by any handler, because none was subscribed to
+/// its type (or key), or because the actor it was addressed to has ended.
+///
+/// Only sent if some actor subscribes to DeadLetter.
+pub struct DeadLetter {
+    pub metadata: Metadata,
+    pub message: Box<dyn Any>,
+}
+
+#[derive(Clone, Copy, Eq, PartialEq, Hash, Debug)]
+pub struct MessageId(u64);
+
+impl fmt::Display for MessageId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// Everything the system knows about a message, besides the message itself.
+#[derive(Clone, Copy, Debug)]
+pub struct Metadata {
+    pub id: MessageId,
+    pub type_name: &'static str,
+    /// The actor it is addressed to, or None for a broadcast.
+    pub recipient: Option<Addr>,
+    /// The actor whose handler sent it, or None if sent from outside the system.
+    pub sender: Option<Addr>,
+    /// The message whose handling caused this one to be sent, if any.
+    pub cause: Option<MessageId>,
+    /// The number of the latest Tick sent into the system, or 0 before the first.
+    pub tick: u64,
+    /// Game time when it was sent, as read by SystemInterface::now.
+    pub sent_at: Instant,
+}
+
+/// A message with its destination, ready to be sent while the concrete type is not known.
+pub struct Envelope {
+    recipient: Option<Addr>,
+    type_name: &'static str,
+    cause: Option<MessageId>,
+    message: Box<dyn Any>,
+}
+
+impl Envelope {
+    /// A message to be broadcast to all actors subscribed to its type.
+    pub fn new<Msg: 'static>(msg: Msg) -> Self {
+        Self {
+            recipient: None,
+            type_name: type_name::<Msg>(),
+            cause: None,
+            message: Box::new(msg),
+        }
+    }
+
+    /// A message to be delivered only to the actor at the given address.
+    pub fn to<Msg: 'static>(addr: Addr, msg: Msg) -> Self {
+        Self {
+            recipient: Some(addr),
+            ..Self::new(msg)
+        }
+    }
+
+    pub(crate) fn from_parts(recipient: Option<Addr>, type_name: &'static str, message: Box<dyn Any>) -> Self {
+        Self {
+            recipient,
+            type_name,
+            cause: None,
+            message,
+        }
+    }
+
+    pub(crate) fn recipient(&self) -> Option<Addr> {
+        self.recipient
+    }
+
+    pub(crate) fn type_name(&self) -> &'static str {
+        self.type_name
+    }
+
+    pub(crate) fn message(&self) -> &dyn Any {
+        &*self.message
+    }
+
+    /// Attribute the message to another cause than the message being handled when it is sent.
+    pub fn caused_by(mut self, cause: MessageId) -> Self {
+        self.cause = Some(cause);
+        self
+    }
+}
+
+/// A message waiting in the queue.
+struct Queued {
+    metadata: Metadata,
+    message: Box<dyn Any>,
+}
+
+pub struct SystemInterface {
+    outbox: VecDeque<Queued>,
+    /// Actors to start, with the actor that created them, if any.
+    new_actors: VecDeque<(StateKey, Option<StateKey>, ActorData)>,
+    /// Allocates the keys of all actors, including ones that have not been started yet.
+    addresses: SlotMap<StateKey, ()>,
+    /// The actor whose handler is currently running, if any.
+    current: Option<StateKey>,
+    /// The message currently being handled, if any.
+    current_message: Option<Metadata>,
+    next_message_id: u64,
+    /// Ids of requests, expectations and timers, counted per system like those of messages.
+    pub(crate) next_request_id: u64,
+    pub(crate) next_expectation: u64,
+    pub(crate) next_timer: u64,
+    /// Number of the latest Tick sent from outside, which messages are stamped with.
+    tick: u64,
+    /// Game time, as of when the last message from outside the system was sent.
+    timeline: Timeline,
+}
+
+impl SystemInterface {
+    /// Enqueue a message for sending; note that the message must be sent with System::handle_one later,
+    pub fn send<Msg: 'static>(&mut self, msg: Msg) {
+        self.send_envelope(Envelope::new(msg));
+    }
+
+    /// Enqueue a message that will only be delivered to the actor at the given address.
+    ///
+    /// The message becomes a DeadLetter if that actor has ended or has no handler for it.
+    pub fn send_to<Msg: 'static>(&mut self, addr: Addr, msg: Msg) {
+        self.send_envelope(Envelope::to(addr, msg));
+    }
+
+    /// Enqueue a message that was wrapped earlier, such as one that was delayed.
+    pub fn send_envelope(&mut self, envelope: Envelope) {
+        let id = MessageId(self.next_message_id);
+        self.next_message_id += 1;
+
+        self.outbox.push_back(Queued {
+            metadata: Metadata {
+                id,
+                type_name: envelope.type_name,
+                recipient: envelope.recipient,
+                sender: self.current.map(Addr),
+                cause: envelope.cause.or_else(|| self.current_message.map(|metadata| metadata.id)),
+                tick: self.tick,
+                sent_at: self.timeline.now(),
+            },
+            message: envelope.message,
+        });
+    }
+
+    /// Schedule an actor to be started once the current message has been handled.
+    ///
+    /// The returned address is usable right away. The actor whose handler is
+    /// running becomes its parent, which matters to actors that escalate panics.
+    pub fn create_actor(&mut self, actor_data: ActorData) -> Addr {
+        let state_key = self.addresses.insert(());
+        self.new_actors.push_back((state_key, self.current, actor_data));
+        Addr(state_key)
+    }
+
+    /// Address of the actor whose handler is currently running, or None outside of handlers.
+    pub fn current_actor(&self) -> Option<Addr> {
+        self.current.map(Addr)
+    }
+
+    /// The game time at which the last message from outside the system was sent, such as the
+    /// latest Tick. Handlers should use this rather than Instant::now(), so that pausing,
+    /// time scaling and replays affect them.
+    pub fn now(&self) -> Instant {
+        self.timeline.now()
+    }
+
+    /// Stop game time, starting from the next message sent from outside the system.
+    pub fn pause(&mut self) {
+        self.timeline.set_paused(true);
+    }
+
+    pub fn resume(&mut self) {
+        self.timeline.set_paused(false);
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.timeline.is_paused()
+    }
+
+    /// Make game time run at the given multiple of the clock of the system.
+    pub fn set_time_scale(&mut self, time_scale: f64) {
+        self.timeline.set_time_scale(time_scale);
+    }
+
+    /// Metadata of the message being handled, or None outside of handlers.
+    pub fn current_message(&self) -> Option<&Metadata> {
+        self.current_message.as_ref()
+    }
+}
+
+struct RunningActor {
+    state: Box<dyn Any>,
+    handlers: Vec<(TypeId, HandlerKey)>,
+    keyed_handlers: Vec<(TypeId, HandlerKey)>,
+    parent: Option<StateKey>,
+    supervision: Supervision,
+}
+
+/// What happens to an actor when one of its handlers panics.
+#[derive(Clone)]
+enum Supervision {
+    /// End the actor and broadcast an ActorFailed.
+    Stop,
+    /// Rebuild the actor with its initial state and handlers, and broadcast an ActorFailed.
+    Restart(Rc<dyn Fn() -> ActorData>),
+    /// End the actor and send the ActorFailed to its parent only.
+    Escalate,
+}
+
+/// Supervision as chosen on an ActorBuilder, before its handlers are complete.
+enum PanicPolicy {
+    Stop,
+    Restart(Rc<dyn Fn() -> Box<dyn Any>>),
+    Escalate,
+}
+
+/// Type-erased index of the keyed handlers for one message type.
+trait KeyedIndex {
+    /// The handlers subscribed to the key of the given message.
+    fn subscribers(&self, msg: &dyn Any) -> Vec<(StateKey, Rc<Handler>)>;
+
+    fn has_subscribers(&self, msg: &dyn Any) -> bool;
+
+    fn get(&self, hkey: HandlerKey) -> Rc<Handler>;
+
+    fn insert(&mut self, key: &dyn Any, state_key: StateKey, handler: Rc<Handler>) -> HandlerKey;
+
+    fn remove(&mut self, hkey: HandlerKey);
+
+    fn is_empty(&self) -> bool;
+}
+
+struct KeyedHandlers<M: Keyed> {
+    handlers: SlotMap<HandlerKey, (M::Key, StateKey, Rc<Handler>)>,
+    by_key: HashMap<M::Key, Vec<HandlerKey>>,
+}
+
+impl<M: Keyed> KeyedHandlers<M> {
+    fn new_index() -> Box<dyn KeyedIndex> {
+        Box::new(Self {
+            handlers: SlotMap::with_key(),
+            by_key: HashMap::new(),
+        })
+    }
+}
+
+impl<M: Keyed> KeyedIndex for KeyedHandlers<M> {
+    fn subscribers(&self, msg: &dyn Any) -> Vec<(StateKey, Rc<Handler>)> {
+        let msg = msg.downcast_ref::<M>().expect("Wrong message type!");
+
+        self.by_key.get(msg.key()).into_iter().flatten().map(|hkey| {
+            let (_, state_key, handler) = &self.handlers[*hkey];
+            (*state_key, handler.clone())
+        }).collect()
+    }
+
+    fn has_subscribers(&self, msg: &dyn Any) -> bool {
+        let msg = msg.downcast_ref::<M>().expect("Wrong message type!");
+
+        self.by_key.contains_key(msg.key())
+    }
+
+    fn get(&self, hkey: HandlerKey) -> Rc<Handler> {
+        self.handlers[hkey].2.clone()
+    }
+
+    fn insert(&mut self, key: &dyn Any, state_key: StateKey, handler: Rc<Handler>) -> HandlerKey {
+        let key = key.downcast_ref::<M::Key>().expect("Wrong key type!").clone();
+        let hkey = self.handlers.insert((key.clone(), state_key, handler));
+        self.by_key.entry(key).or_default().push(hkey);
+        hkey
+    }
+
+    fn remove(&mut self, hkey: HandlerKey) {
+        let (key, _, _) = self.handlers.remove(hkey).expect("Invalid keyed handler reference.");
+        let subscribed = self.by_key.get_mut(&key).expect("Keyed handler missing from index.");
+        subscribed.retain(|h| *h != hkey);
+        if subscribed.is_empty() {
+            self.by_key.remove(&key);
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.handlers.is_empty()
+    }
+}
+
+/// Facade struct that contains a full, working actor system.
+pub struct System {
+    state_store: SecondaryMap<StateKey, RunningActor>,
+    handlers: HashMap<TypeId, SlotMap<HandlerKey, (StateKey, Rc<Handler>)>>,
+    keyed_handlers: HashMap<TypeId, Box<dyn KeyedIndex>>,
+    /// Actors that handle_batch may run on worker threads.
+    send_actors: SendActors,
+    /// Number of undelivered messages per type name.
+    dead_letters: HashMap<&'static str, usize>,
+    /// Whether to panic on dead letters.
+    strict: bool,
+    /// Every message handled while tracing is on.
+    trace: Option<Trace>,
+    /// Recording or replay of the messages sent from outside, if either is in progress.
+    session: Option<Session>,
+    pub input_interface: SystemInterface,
+}
+
+/// A handler that only receives messages carrying a particular key.
+#[derive(Clone)]
+struct KeyedRegistration {
+    typ: TypeId,
+    key: Rc<dyn Any>,
+    new_index: fn() -> Box<dyn KeyedIndex>,
+    handler: Rc<Handler>,
+}
+
+pub struct ActorBuilder<S> {
+    init_state: Option<S>,
+    handlers: Vec<(TypeId, Rc<Handler>)>,
+    keyed_handlers: Vec<KeyedRegistration>,
+    on_panic: PanicPolicy,
+}
+
+pub struct ActorData {
+    /// None for behaviors that keep the state of the actor switching to them.
+    init_state: Option<Box<dyn Any>>,
+    handlers: Vec<(TypeId, Rc<Handler>)>,
+    keyed_handlers: Vec<KeyedRegistration>,
+    /// Ignored when switching behaviors; an actor keeps the supervision it was created with.
+    supervision: Supervision,
+}
+
+impl fmt::Debug for ActorData {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ActorData")
+            .field("has_state", &self.init_state.is_some())
+            .field("handlers", &self.handlers.len())
+            .field("keyed_handlers", &self.keyed_handlers.len())
+            .finish()
+    }
+}
+
+#[derive(Debug)]
+pub enum Fate {
+    Keep,
+    End,
+    /// Atomically replace all handlers of the actor (and its state, if the new
+    /// behavior has one) once the current message has been handled.
+    Become(ActorData),
+}
+
+impl<S: 'static> ActorBuilder<S> {
+    pub fn new(init_state: S) -> Self {
+        Self {
+            init_state: Some(init_state),
+            handlers: vec![],
+            keyed_handlers: vec![],
+            on_panic: PanicPolicy::Stop,
+        }
+    }
+
+    /// Start building a behavior for Fate::Become that keeps the current state of the actor.
+    ///
+    /// That state must be of type S, or the handlers will panic.
+    pub fn behavior() -> Self {
+        Self {
+            init_state: None,
+            handlers: vec![],
+            keyed_handlers: vec![],
+            on_panic: PanicPolicy::Stop,
+        }
+    }
+
+    pub fn with_handler<M: 'static, F: Fn(&mut S, &M, &mut SystemInterface) -> Fate + 'static>(mut self, handler: F) -> Self {
+        self.handlers.push((TypeId::of::<M>(), Self::erase(handler)));
+        self
+    }
+
+    /// Add a handler that only receives the messages of type M whose key equals the given one.
+    ///
+    /// Unlike filtering inside a regular handler, this does not cost anything
+    /// for messages about other keys.
+    pub fn with_keyed_handler<M: Keyed, F: Fn(&mut S, &M, &mut SystemInterface) -> Fate + 'static>(mut self, key: M::Key, handler: F) -> Self {
+        self.keyed_handlers.push(KeyedRegistration {
+            typ: TypeId::of::<M>(),
+            key: Rc::new(key),
+            new_index: KeyedHandlers::<M>::new_index,
+            handler: Self::erase(handler),
+        });
+        self
+    }
+
+    /// Instead of ending the actor when one of its handlers panics, send its parent
+    /// (the actor that created it) an ActorFailed, so that it can decide what to do.
+    pub fn escalate_on_panic(mut self) -> Self {
+        self.on_panic = PanicPolicy::Escalate;
+        self
+    }
+
+    /// Instead of ending the actor when one of its handlers panics, start it over
+    /// with its initial handlers and whatever state `state` returns at that point.
+    pub fn restart_on_panic_with(mut self, state: impl Fn() -> S + 'static) -> Self {
+        self.on_panic = PanicPolicy::Restart(Rc::new(move || Box::new(state())));
+        self
+    }
+
+    fn erase<M: 'static, F: Fn(&mut S, &M, &mut SystemInterface) -> Fate + 'static>(handler: F) -> Rc<Handler> {
+        Rc::new(move |state: &mut dyn Any, message: &dyn Any, outbox: &mut SystemInterface| {
+            let state = state.downcast_mut::<S>().expect("Wrong state type!");
+            let message = message.downcast_ref::<M>().expect("Wrong message type!");
+            handler(state, message, outbox)
+        })
+    }
+
+    pub fn build(self) -> ActorData {
+        let supervision = match self.on_panic {
+            PanicPolicy::Stop => Supervision::Stop,
+            PanicPolicy::Escalate => Supervision::Escalate,
+            PanicPolicy::Restart(init_state) => {
+                let handlers = self.handlers.clone();
+                let keyed_handlers = self.keyed_handlers.clone();
+                Supervision::Restart(Rc::new(move || ActorData {
+                    init_state: Some(init_state()),
+                    handlers: handlers.clone(),
+                    keyed_handlers: keyed_handlers.clone(),
+                    supervision: Supervision::Stop,
+                }))
+            }
+        };
+
+        ActorData {
+            init_state: self.init_state.map(|state| Box::new(state) as Box<dyn Any>),
+            handlers: self.handlers,
+            keyed_handlers: self.keyed_handlers,
+            supervision,
+        }
+    }
+}
+
+impl<S: Clone + 'static> ActorBuilder<S> {
+    /// Instead of ending the actor when one of its handlers panics, start it over
+    /// with a clone of its initial state and its initial handlers.
+    pub fn restart_on_panic(mut self) -> Self {
+        let init_state = self.init_state.clone().expect("Only actors with an initial state can be restarted.");
+        self.on_panic = PanicPolicy::Restart(Rc::new(move || Box::new(init_state.clone())));
+        self
+    }
+}
+
+impl System {
+    /// Initialize an empty actor system
+    pub fn new() -> Self {
+        Self {
+            state_store: SecondaryMap::new(),
+            handlers: Default::default(),
+            keyed_handlers: Default::default(),
+            send_actors: Default::default(),
+            dead_letters: Default::default(),
+            strict: false,
+            trace: None,
+            session: None,
+            input_interface: SystemInterface {
+                outbox: Default::default(),
+                new_actors: Default::default(),
+                addresses: SlotMap::with_key(),
+                current: None,
+                current_message: None,
+                next_message_id: 0,
+                next_request_id: 0,
+                next_expectation: 0,
+                next_timer: 0,
+                tick: 0,
+                timeline: Timeline::new(Box::new(RealClock)),
+            },
+        }
+    }
+
+    pub fn create_actor(&mut self, actor_data: ActorData) -> Addr {
+        let state_key = self.input_interface.addresses.insert(());
+        self.start_actor(state_key, None, actor_data);
+        Addr(state_key)
+    }
+
+    /// Create an actor that handle_batch may run on a worker thread, next to the
+    /// actors that have to stay on the main thread. Any actor that keeps no Rc or
+    /// other main-thread state, such as the scene nodes of a window, can be one.
+    pub fn create_send_actor(&mut self, actor_data: SendActorData) -> Addr {
+        let state_key = self.input_interface.addresses.insert(());
+        self.send_actors.insert(state_key, actor_data);
+        Addr(state_key)
+    }
+
+    fn start_actor(&mut self, state_key: StateKey, parent: Option<StateKey>, mut actor_data: ActorData) {
+        self.state_store.insert(state_key, RunningActor {
+            state: actor_data.init_state.take().expect("Actor started without an initial state."),
+            handlers: vec![],
+            keyed_handlers: vec![],
+            parent,
+            supervision: actor_data.supervision.clone(),
+        });
+
+        self.register_handlers(state_key, actor_data);
+    }
+
+    fn register_handlers(&mut self, state_key: StateKey, actor_data: ActorData) {
+        for (typ, handler) in actor_data.handlers {
+            let handler_key = self.handlers.entry(typ).or_default().insert((
+                state_key, handler
+            ));
+            self.state_store[state_key].handlers.push((typ, handler_key));
+        }
+
+        for registration in actor_data.keyed_handlers {
+            let handler_key = self.keyed_handlers.entry(registration.typ)
+                .or_insert_with(registration.new_index)
+                .insert(&*registration.key, state_key, registration.handler);
+            self.state_store[state_key].keyed_handlers.push((registration.typ, handler_key));
+        }
+    }
+
+    /// Broadcast a message into the system, to be received by all actors subscribed to that type.
+    pub fn send<Msg: 'static>(&mut self, msg: Msg) {
+        self.send_from_outside(Envelope::new(msg));
+    }
+
+    /// Send a message into the system, to be received only by the actor at the given address.
+    pub fn send_to<Msg: 'static>(&mut self, addr: Addr, msg: Msg) {
+        self.send_from_outside(Envelope::to(addr, msg));
+    }
+
+    fn send_from_outside(&mut self, mut envelope: Envelope) {
+        let now = self.input_interface.timeline.sample();
+
+        if let Some(session) = &mut self.session {
+            let (passed, recorded_now) = session.pass(envelope, now);
+            envelope = passed;
+            if recorded_now != now {
+                self.input_interface.timeline.set(recorded_now);
+            }
+
+            if session.is_finished() {
+                self.session = None;
+            }
+        }
+
+        if let Some(tick) = envelope.message.downcast_ref::<Tick>() {
+            self.input_interface.tick = tick.number;
+        }
+
+        self.input_interface.send_envelope(envelope);
+    }
+
+    /// Replace the clock that game time is derived from, which is the wall clock by default.
+    pub fn set_clock(&mut self, clock: impl Clock + 'static) {
+        self.input_interface.timeline = Timeline::new(Box::new(clock));
+    }
+
+    /// Record the messages of the given types sent from outside the system from now on,
+    /// with the time they were sent at, such that the session can be replayed.
+    ///
+    /// The seed is only stored, for the game to seed its random number generators with.
+    pub fn start_recording(&mut self, seed: u64, types: RecordedTypes) {
+        self.session = Some(Session::recording(seed, types, self.input_interface.now()));
+    }
+
+    pub fn stop_recording(&mut self) -> Option<Recording> {
+        self.session.take().and_then(Session::into_recording)
+    }
+
+    /// Play a recording back, which should start from the same state as when it was recorded,
+    /// with the same program sending the same number of messages from outside the system.
+    ///
+    /// Until the recording runs out, messages of the recorded types sent from outside the system
+    /// are swapped for the recorded ones, and SystemInterface::now returns the recorded time.
+    pub fn start_replay(&mut self, recording: Recording, types: RecordedTypes) {
+        let session = Session::replaying(recording, types, self.input_interface.now());
+        if !session.is_finished() {
+            self.session = Some(session);
+        }
+    }
+
+    pub fn is_replaying(&self) -> bool {
+        self.session.as_ref().is_some_and(Session::is_replaying)
+    }
+
+    /// Takes one message from the queue and feeds it to the appropriate handler.
+    ///
+    /// Returns false if the internal queue was empty when calling the method.
+    pub fn handle_one(&mut self) -> bool {
+        if let Some(queued) = self.input_interface.outbox.pop_front() {
+            if let Some(trace) = &mut self.trace {
+                trace.record(queued.metadata);
+            }
+
+            if !self.is_deliverable(&queued) {
+                self.dead_letter(queued);
+                return true;
+            }
+
+            self.dispatch(&queued);
+
+            for state_key in self.send_actors.recipients(&*queued.message, queued.metadata.recipient) {
+                let mut actor = self.send_actors.take(state_key);
+                let run = actor.run(Addr(state_key), vec![(0, SharedMessage::new(&*queued.message))]);
+                self.send_actors.put_back(state_key, actor);
+                self.finish_send_run(state_key, run, |_| queued.metadata);
+            }
+
+            true
+        } else {
+            self.input_interface.timeline.tick();
+            false
+        }
+    }
+
+    /// Handle every message that is currently queued, running the Send actors on up
+    /// to `workers` threads while the other actors run on this one.
+    ///
+    /// Each actor still receives its messages in the order they were sent. The messages
+    /// sent by Send actors are queued after those sent by the others during the batch.
+    ///
+    /// Returns false if the internal queue was empty when calling the method.
+    pub fn handle_batch(&mut self, workers: usize) -> bool {
+        if self.input_interface.outbox.is_empty() {
+            self.input_interface.timeline.tick();
+            return false;
+        }
+
+        let mut batch = vec![];
+
+        for queued in std::mem::take(&mut self.input_interface.outbox) {
+            if let Some(trace) = &mut self.trace {
+                trace.record(queued.metadata);
+            }
+
+            if self.is_deliverable(&queued) {
+                batch.push(queued);
+            } else {
+                self.dead_letter(queued);
+            }
+        }
+
+        // The mail of every Send actor, as indices into the batch, in order of first delivery.
+        let mut mail: Vec<(StateKey, Vec<usize>)> = vec![];
+        let mut mailboxes: HashMap<StateKey, usize> = HashMap::new();
+
+        for (index, queued) in batch.iter().enumerate() {
+            for state_key in self.send_actors.recipients(&*queued.message, queued.metadata.recipient) {
+                let mailbox = *mailboxes.entry(state_key).or_insert_with(|| {
+                    mail.push((state_key, vec![]));
+                    mail.len() - 1
+                });
+                mail[mailbox].1.push(index);
+            }
+        }
+
+        let workers = workers.max(1);
+        let mut jobs: Vec<Vec<_>> = (0..workers).map(|_| vec![]).collect();
+
+        for (i, (state_key, indices)) in mail.into_iter().enumerate() {
+            let actor = self.send_actors.take(state_key);
+            let messages: Vec<_> = indices.into_iter().map(|index| (index, SharedMessage::new(&*batch[index].message))).collect();
+            jobs[i % workers].push((state_key, actor, messages));
+        }
+
+        let mut runs = thread::scope(|scope| {
+            let handles: Vec<_> = jobs.into_iter().filter(|job| !job.is_empty()).map(|job| {
+                scope.spawn(move || {
+                    job.into_iter().map(|(state_key, mut actor, messages)| {
+                        let run = actor.run(Addr(state_key), messages);
+                        (state_key, actor, run)
+                    }).collect::<Vec<_>>()
+                })
+            }).collect();
+
+            for queued in &batch {
+                self.dispatch(queued);
+            }
+
+            handles.into_iter()
+                .flat_map(|handle| handle.join().expect("Worker thread panicked."))
+                .collect::<Vec<_>>()
+        });
+
+        // Sorting makes the order of the merged messages independent of the number of workers.
+        runs.sort_by_key(|(state_key, _, _)| mailboxes[state_key]);
+
+        for (state_key, actor, run) in runs {
+            self.send_actors.put_back(state_key, actor);
+            self.finish_send_run(state_key, run, |index| batch[index].metadata);
+        }
+
+        true
+    }
+
+    /// Feed a message to the actors that are not Send actors, and apply their fates.
+    fn dispatch(&mut self, queued: &Queued) {
+        self.input_interface.current_message = Some(queued.metadata);
+
+        let outcomes = match queued.metadata.recipient {
+            None => self.broadcast(&*queued.message),
+            Some(Addr(state_key)) => self.deliver(state_key, &*queued.message),
+        };
+
+        self.input_interface.current_message = None;
+
+        for (state_key, outcome) in outcomes {
+            match outcome {
+                Ok(Keep) => {}
+                Ok(End) => self.stop_actor(state_key),
+                Ok(Become(behavior)) => self.reshape_actor(state_key, behavior),
+                Err(payload) => self.supervise(state_key, queued.metadata.type_name, payload),
+            }
+        }
+
+        while let Some((state_key, parent, actor_data)) = self.input_interface.new_actors.pop_front() {
+            self.start_actor(state_key, parent, actor_data);
+        }
+    }
+
+    /// Queue what a Send actor sent, attributed to the messages it was handling, and end it if it is done.
+    fn finish_send_run(&mut self, state_key: StateKey, run: SendRun, metadata: impl Fn(usize) -> Metadata) {
+        self.input_interface.current = Some(state_key);
+
+        for (index, sent) in run.sent {
+            self.input_interface.current_message = Some(metadata(index));
+            for envelope in sent {
+                self.input_interface.send_envelope(envelope.into());
+            }
+        }
+
+        self.input_interface.current = None;
+        self.input_interface.current_message = None;
+
+        if let Some(failure) = run.ended {
+            self.send_actors.remove(state_key);
+            self.input_interface.addresses.remove(state_key);
+
+            if let Some((message_type, payload)) = failure {
+                self.input_interface.send(ActorFailed {
+                    actor: Addr(state_key),
+                    message_type,
+                    payload,
+                });
+            }
+        }
+    }
+
+    /// Number of messages, per type name, that could not be delivered to any handler so far.
+    pub fn dead_letter_counts(&self) -> &HashMap<&'static str, usize> {
+        &self.dead_letters
+    }
+
+    /// In strict mode, undelivered messages cause a panic rather than a DeadLetter.
+    ///
+    /// Meant for tests, where a message nobody listens to is usually a bug.
+    pub fn set_strict(&mut self, strict: bool) {
+        self.strict = strict;
+    }
+
+    /// Start recording the metadata of every message handled from now on, discarding any earlier trace.
+    pub fn start_tracing(&mut self) {
+        self.trace = Some(Trace::default());
+    }
+
+    /// Stop recording, returning what was recorded.
+    pub fn stop_tracing(&mut self) -> Option<Trace> {
+        self.trace.take()
+    }
+
+    pub fn trace(&self) -> Option<&Trace> {
+        self.trace.as_ref()
+    }
+
+    fn is_deliverable(&self, queued: &Queued) -> bool {
+        let typ = queued.message.deref().type_id();
+
+        if !self.send_actors.recipients(&*queued.message, queued.metadata.recipient).is_empty() {
+            return true;
+        }
+
+        match queued.metadata.recipient {
+            None => self.handlers.contains_key(&typ) || self.keyed_handlers.get(&typ)
+                .is_some_and(|index| index.has_subscribers(&*queued.message)),
+            Some(Addr(state_key)) => self.state_store.get(state_key).is_some_and(|actor| {
+                actor.handlers.iter().chain(actor.keyed_handlers.iter()).any(|(t, _)| *t == typ)
+            }),
+        }
+    }
+
+    fn dead_letter(&mut self, queued: Queued) {
+        if self.strict {
+            panic!("Message of type {} was not delivered to any handler.", queued.metadata.type_name);
+        }
+
+        *self.dead_letters.entry(queued.metadata.type_name).or_default() += 1;
+
+        // Dead letters about dead letters would loop forever.
+        if !queued.message.is::<DeadLetter>() && self.handlers.contains_key(&TypeId::of::<DeadLetter>()) {
+            self.input_interface.send(DeadLetter {
+                metadata: queued.metadata,
+                message: queued.message,
+            });
+        }
+    }
+
+    fn stop_actor(&mut self, state_key: StateKey) {
+        // An actor may have ended through an earlier handler for the same message.
+        if let Some(corpse) = self.state_store.remove(state_key) {
+            self.input_interface.addresses.remove(state_key);
+            self.unregister_handlers(corpse.handlers, corpse.keyed_handlers);
+        }
+    }
+
+    fn reshape_actor(&mut self, state_key: StateKey, mut behavior: ActorData) {
+        if let Some(actor) = self.state_store.get_mut(state_key) {
+            if let Some(state) = behavior.init_state.take() {
+                actor.state = state;
+            }
+            let handlers = std::mem::take(&mut actor.handlers);
+            let keyed_handlers = std::mem::take(&mut actor.keyed_handlers);
+            self.unregister_handlers(handlers, keyed_handlers);
+            self.register_handlers(state_key, behavior);
+        }
+    }
+
+    /// Apply the supervision strategy of an actor whose handler panicked.
+    fn supervise(&mut self, state_key: StateKey, message_type: &'static str, payload: String) {
+        let (supervision, parent) = match self.state_store.get(state_key) {
+            Some(actor) => (actor.supervision.clone(), actor.parent),
+            None => return,
+        };
+
+        let report = ActorFailed {
+            actor: Addr(state_key),
+            message_type,
+            payload,
+        };
+
+        match supervision {
+            Supervision::Stop => {
+                self.stop_actor(state_key);
+                self.input_interface.send(report);
+            }
+            Supervision::Restart(rebuild) => {
+                self.reshape_actor(state_key, rebuild());
+                self.input_interface.send(report);
+            }
+            Supervision::Escalate => {
+                self.stop_actor(state_key);
+                match parent.filter(|parent| self.state_store.contains_key(*parent)) {
+                    Some(parent) => self.input_interface.send_to(Addr(parent), report),
+                    None => self.input_interface.send(report),
+                }
+            }
+        }
+    }
+
+    fn unregister_handlers(&mut self, handlers: Vec<(TypeId, HandlerKey)>, keyed_handlers: Vec<(TypeId, HandlerKey)>) {
+        for (typ, hkey) in handlers {
+            self.handlers.get_mut(&typ).expect("Invalid handler reference in state store.").remove(hkey);
+            if self.handlers[&typ].is_empty() {
+                self.handlers.remove(&typ);
+            }
+        }
+        for (typ, hkey) in keyed_handlers {
+            let index = self.keyed_handlers.get_mut(&typ).expect("Invalid handler reference in state store.");
+            index.remove(hkey);
+            if index.is_empty() {
+                self.keyed_handlers.remove(&typ);
+            }
+        }
+    }
+
+    /// Feed a message to every handler subscribed to its type (or to its key),
+    /// returning the outcomes for the actors that did not just Keep going.
+    ///
+    /// Fates are applied after dispatch, so that the handler tables do not change while iterating them.
+    fn broadcast(&mut self, msg: &dyn Any) -> Vec<(StateKey, Outcome)> {
+        let mut outcomes = vec![];
+
+        if let Some(handlers) = self.handlers.get_mut(&msg.type_id()) {
+            let state_store = &mut self.state_store;
+            let input_interface = &mut self.input_interface;
+
+            for (_, (state_key, handler)) in handlers {
+                let state = state_store[*state_key].state.deref_mut();
+                input_interface.current = Some(*state_key);
+                match run_handler(&**handler, state, msg, input_interface) {
+                    Ok(Keep) => {}
+                    outcome => outcomes.push((*state_key, outcome)),
+                }
+            }
+        }
+
+        if let Some(index) = self.keyed_handlers.get(&msg.type_id()) {
+            for (state_key, handler) in index.subscribers(msg) {
+                let state = self.state_store[state_key].state.deref_mut();
+                self.input_interface.current = Some(state_key);
+                match run_handler(&*handler, state, msg, &mut self.input_interface) {
+                    Ok(Keep) => {}
+                    outcome => outcomes.push((state_key, outcome)),
+                }
+            }
+        }
+
+        self.input_interface.current = None;
+
+        outcomes
+    }
+
+    /// Feed a message to the handlers of a single actor, returning its outcome unless it just Keeps going.
+    fn deliver(&mut self, state_key: StateKey, msg: &dyn Any) -> Vec<(StateKey, Outcome)> {
+        let typ = msg.type_id();
+
+        let actor = match self.state_store.get_mut(state_key) {
+            Some(actor) => actor,
+            None => return vec![],
+        };
+
+        let handlers = &self.handlers;
+        let keyed_handlers = &self.keyed_handlers;
+
+        let matching: Vec<Rc<Handler>> = actor.handlers.iter()
+            .filter(|(t, _)| *t == typ)
+            .map(|(_, hkey)| handlers[&typ][*hkey].1.clone())
+            .chain(actor.keyed_handlers.iter()
+                .filter(|(t, _)| *t == typ)
+                .map(|(_, hkey)| keyed_handlers[&typ].get(*hkey)))
+            .collect();
+
+        self.input_interface.current = Some(state_key);
+
+        let mut outcomes = vec![];
+
+        for handler in matching {
+            match run_handler(&*handler, actor.state.deref_mut(), msg, &mut self.input_interface) {
+                Ok(Keep) => {}
+                outcome @ Ok(End) | outcome @ Err(_) => {
+                    outcomes.push((state_key, outcome));
+                    break;
+                }
+                outcome => outcomes.push((state_key, outcome)),
+            }
+        }
+
+        self.input_interface.current = None;
+
+        outcomes
+    }
+}
+
+impl Default for System {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The fate returned by a handler, or the payload of the panic it raised.
+type Outcome = Result<Fate, String>;
+
+fn run_handler(handler: &Handler, state: &mut dyn Any, msg: &dyn Any, input_interface: &mut SystemInterface) -> Outcome {
+    panic::catch_unwind(AssertUnwindSafe(|| handler(state, msg, input_interface))).map_err(panic_message)
+}
+
+pub(crate) fn panic_message(payload: Box<dyn Any + Send>) -> String {
+    payload.downcast_ref::<&str>().map(|msg| msg.to_string())
+        .or_else(|| payload.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "<non-string panic payload>".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+    use std::sync::mpsc::{channel, Sender};
+    use std::time::Duration;
+
+    use crate::actors::Fate::Keep;
+    use crate::clock::FixedTimestep;
+
+    use super::*;
+
+    #[test]
+    fn hello_world() {
+        struct Tick;
+
+        let (tx, rx) = channel();
+
+        let mut system = System::new();
+
+        system.create_actor(ActorBuilder::new(0)
+            .with_handler(|i, _: &Tick, outbox| {
+                *i += 1;
+
+                outbox.send(*i);
+
+                Keep
+            }).build());
+
+        system.create_actor(ActorBuilder::new(tx)
+            .with_handler(|tx, i: &i32, _| {
+                tx.send(*i).unwrap();
+
+                Keep
+            }).build());
+
+        system.send(Tick);
+        system.send(Tick);
+        system.send(Tick);
+        system.send(Tick);
+
+        while system.handle_one() {};
+
+        assert_eq!(rx.recv().unwrap(), 1);
+        assert_eq!(rx.recv().unwrap(), 2);
+        assert_eq!(rx.recv().unwrap(), 3);
+        assert_eq!(rx.recv().unwrap(), 4);
+    }
+
+    #[test]
+    fn send_to_address() {
+        struct Poke;
+
+        let (tx, rx) = channel();
+
+        let mut system = System::new();
+
+        let addrs: Vec<Addr> = (0..3).map(|i| {
+            let tx = tx.clone();
+            system.create_actor(ActorBuilder::new(i)
+                .with_handler(move |i, _: &Poke, _| {
+                    tx.send(*i).unwrap();
+                    Keep
+                }).build())
+        }).collect();
+
+        system.send_to(addrs[1], Poke);
+
+        while system.handle_one() {};
+
+        assert_eq!(rx.try_iter().collect::<Vec<_>>(), vec![1]);
+    }
+
+    #[test]
+    fn keyed_handlers() {
+        #[derive(Clone, Copy, Eq, PartialEq, Hash, Debug)]
+        struct Id(u32);
+
+        struct Poke(Id);
+
+        impl Keyed for Poke {
+            type Key = Id;
+
+            fn key(&self) -> &Id {
+                &self.0
+            }
+        }
+
+        let (tx, rx) = channel();
+
+        let mut system = System::new();
+
+        for i in 0..3 {
+            let tx = tx.clone();
+            system.create_actor(ActorBuilder::new(0)
+                .with_keyed_handler(Id(i), move |pokes, _: &Poke, _| {
+                    *pokes += 1;
+                    tx.send((i, *pokes)).unwrap();
+                    if *pokes == 2 { End } else { Keep }
+                }).build());
+        }
+
+        system.send(Poke(Id(1)));
+        system.send(Poke(Id(2)));
+        system.send(Poke(Id(1)));
+        system.send(Poke(Id(1)));
+        system.send(Poke(Id(7)));
+
+        while system.handle_one() {};
+
+        assert_eq!(rx.try_iter().collect::<Vec<_>>(), vec![(1, 1), (2, 1), (1, 2)]);
+    }
+
+    #[test]
+    fn become_switches_handlers() {
+        struct Toggle;
+
+        struct Poke;
+
+        fn off() -> ActorData {
+            ActorBuilder::<Sender<&'static str>>::behavior()
+                .with_handler(|_, _: &Toggle, _| Become(on()))
+                .build()
+        }
+
+        fn on() -> ActorData {
+            ActorBuilder::<Sender<&'static str>>::behavior()
+                .with_handler(|_, _: &Toggle, _| Become(off()))
+                .with_handler(|tx, _: &Poke, _| {
+                    tx.send("poked").unwrap();
+                    Keep
+                })
+                .build()
+        }
+
+        let (tx, rx) = channel::<&'static str>();
+
+        let mut system = System::new();
+
+        system.create_actor(ActorBuilder::new(tx)
+            .with_handler(|_, _: &Toggle, _| Become(on()))
+            .build());
+
+        system.send(Poke);
+        system.send(Toggle);
+        system.send(Poke);
+        system.send(Toggle);
+        system.send(Poke);
+
+        while system.handle_one() {};
+
+        assert_eq!(rx.try_iter().collect::<Vec<_>>(), vec!["poked"]);
+    }
+
+    #[test]
+    fn restart_on_panic() {
+        struct Count;
+
+        struct Explode;
+
+        let (tx, rx) = channel();
+        let failed_tx = tx.clone();
+
+        let mut system = System::new();
+
+        system.create_actor(ActorBuilder::new(0)
+            .with_handler(move |i, _: &Count, _| {
+                *i += 1;
+                tx.send(format!("{}", i)).unwrap();
+                Keep
+            })
+            .with_handler(|_, _: &Explode, _| panic!("Boom!"))
+            .restart_on_panic()
+            .build());
+
+        system.create_actor(ActorBuilder::new(())
+            .with_handler(move |_, failure: &ActorFailed, _| {
+                failed_tx.send(failure.payload.clone()).unwrap();
+                Keep
+            }).build());
+
+        system.send(Count);
+        system.send(Count);
+        system.send(Explode);
+        system.send(Count);
+
+        while system.handle_one() {};
+
+        assert_eq!(rx.try_iter().collect::<Vec<_>>(), vec!["1", "2", "1", "Boom!"]);
+    }
+
+    #[test]
+    fn restart_on_panic_with_state() {
+        struct Count;
+
+        struct Explode;
+
+        let (tx, rx) = channel();
+
+        let mut system = System::new();
+
+        let saved = Rc::new(Cell::new(0));
+        let restart_from = saved.clone();
+
+        system.create_actor(ActorBuilder::new(0)
+            .with_handler(move |i, _: &Count, _| {
+                *i += 1;
+                saved.set(*i * 10);
+                tx.send(format!("{}", i)).unwrap();
+                Keep
+            })
+            .with_handler(|_, _: &Explode, _| panic!("Boom!"))
+            .restart_on_panic_with(move || restart_from.get())
+            .build());
+
+        system.send(Count);
+        system.send(Count);
+        system.send(Explode);
+        system.send(Count);
+
+        while system.handle_one() {};
+
+        assert_eq!(rx.try_iter().collect::<Vec<_>>(), vec!["1", "2", "21"]);
+    }
+
+    #[test]
+    fn escalate_to_parent() {
+        struct Spawn;
+
+        struct Explode;
+
+        let (tx, rx) = channel();
+
+        let mut system = System::new();
+
+        system.create_actor(ActorBuilder::new(())
+            .with_handler(|_, _: &Spawn, outbox| {
+                outbox.create_actor(ActorBuilder::new(())
+                    .with_handler(|_, _: &Explode, _| panic!("Boom!"))
+                    .escalate_on_panic()
+                    .build());
+                Keep
+            })
+            .with_handler(move |_, failure: &ActorFailed, _| {
+                tx.send(failure.message_type).unwrap();
+                Keep
+            }).build());
+
+        system.send(Spawn);
+        system.send(Explode);
+        system.send(Explode);
+
+        while system.handle_one() {};
+
+        assert_eq!(rx.try_iter().collect::<Vec<_>>(), vec![type_name::<Explode>()]);
+    }
+
+    #[test]
+    fn dead_letters() {
+        struct Unheard;
+
+        struct Heard;
+
+        let (tx, rx) = channel();
+
+        let mut system = System::new();
+
+        let listener = system.create_actor(ActorBuilder::new(())
+            .with_handler(|_, _: &Heard, _| End)
+            .build());
+
+        system.create_actor(ActorBuilder::new(())
+            .with_handler(move |_, dead: &DeadLetter, _| {
+                tx.send((dead.metadata.type_name, dead.metadata.recipient)).unwrap();
+                Keep
+            }).build());
+
+        system.send(Unheard);
+        system.send_to(listener, Heard);
+        system.send_to(listener, Heard);
+
+        while system.handle_one() {};
+
+        assert_eq!(rx.try_iter().collect::<Vec<_>>(), vec![
+            (type_name::<Unheard>(), None),
+            (type_name::<Heard>(), Some(listener)),
+        ]);
+        assert_eq!(system.dead_letter_counts()[type_name::<Unheard>()], 1);
+        assert_eq!(system.dead_letter_counts()[type_name::<Heard>()], 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "was not delivered")]
+    fn strict_mode() {
+        struct Unheard;
+
+        let mut system = System::new();
+        system.set_strict(true);
+
+        system.send(Unheard);
+
+        while system.handle_one() {};
+    }
+
+    #[test]
+    fn envelope_metadata() {
+        struct Request;
+
+        struct Response;
+
+        let (tx, rx) = channel();
+
+        let mut system = System::new();
+
+        let responder = system.create_actor(ActorBuilder::new(())
+            .with_handler(|_, _: &Request, outbox| {
+                outbox.send(Response);
+                Keep
+            }).build());
+
+        system.create_actor(ActorBuilder::new(())
+            .with_handler(move |_, _: &Response, outbox| {
+                tx.send(*outbox.current_message().unwrap()).unwrap();
+                Keep
+            }).build());
+
+        system.send(Request);
+
+        while system.handle_one() {};
+
+        let response = rx.recv().unwrap();
+        assert_eq!(response.type_name, type_name::<Response>());
+        assert_eq!(response.sender, Some(responder));
+        assert_eq!(response.cause, Some(MessageId(0)));
+        assert_eq!(response.tick, 0);
+    }
+
+    #[test]
+    fn stamped_with_the_latest_tick() {
+        struct Echo;
+
+        let (tx, rx) = channel();
+
+        let mut system = System::new();
+        let mut timestep = FixedTimestep::new(Duration::from_millis(10));
+
+        system.create_actor(ActorBuilder::new(())
+            .with_handler(|_, _: &Tick, outbox| {
+                outbox.send(Echo);
+                Keep
+            })
+            .with_handler(move |_, _: &Echo, outbox| {
+                tx.send(outbox.current_message().unwrap().tick).unwrap();
+                Keep
+            }).build());
+
+        for _ in 0..3 {
+            system.send(timestep.step());
+            // Running the queue empty more than once per Tick does not count as another.
+            while system.handle_one() {};
+            while system.handle_one() {};
+        }
+
+        assert_eq!(rx.try_iter().collect::<Vec<_>>(), vec![0, 1, 2]);
+    }
+}
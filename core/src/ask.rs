@@ -0,0 +1,206 @@
+use std::marker::PhantomData;
+use std::time::Duration;
+
+use crate::actors::{ActorBuilder, Addr, SystemInterface};
+use crate::actors::Fate::End;
+use crate::delay::{CancelTimer, delay_to, TimerHandle};
+
+/// Correlates a reply with the request it answers.
+#[derive(Clone, Copy, Eq, PartialEq, Hash, Debug)]
+pub struct RequestId(u64);
+
+/// Where to send the answer to a request; request messages carry one of these.
+pub struct ReplyTo<T> {
+    addr: Addr,
+    id: RequestId,
+    /// The timeout of the request, if it has one.
+    timer: Option<TimerHandle>,
+    _reply: PhantomData<fn(T)>,
+}
+
+impl<T> Clone for ReplyTo<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for ReplyTo<T> {}
+
+impl<T: 'static> ReplyTo<T> {
+    /// Send the answer, and cancel the timeout of the request so that it does not arrive too.
+    pub fn reply(&self, outbox: &mut SystemInterface, value: T) {
+        outbox.send_to(self.addr, Reply { id: self.id, value });
+        if let Some(timer) = self.timer {
+            outbox.send(CancelTimer(timer));
+        }
+    }
+}
+
+/// The answer to a request, delivered to the actor that asked.
+pub struct Reply<T> {
+    pub id: RequestId,
+    pub value: T,
+}
+
+/// Delivered to the actor that asked instead of a Reply when none arrived in time.
+#[derive(Clone, Copy, Eq, PartialEq, Hash, Debug)]
+pub struct AskTimedOut(pub RequestId);
+
+impl SystemInterface {
+    /// Send a request to the actor at `to`, built around the address its reply should go to.
+    ///
+    /// The reply arrives at the asking actor as a `Reply<T>` carrying the returned id,
+    /// or, if a timeout is given and it expires first, as an `AskTimedOut` with that id.
+    /// Replying cancels the timeout, but a reply sent after it went off still arrives.
+    ///
+    /// Must be called from within a handler; use ask_then otherwise.
+    pub fn ask<T: 'static, Req: 'static>(&mut self, to: Addr, request: impl FnOnce(ReplyTo<T>) -> Req, timeout: Option<Duration>) -> RequestId {
+        let requester = self.current_actor().expect("ask called outside of a handler.");
+        let id = self.next_request_id();
+
+        // The timer is set before the request goes out, so that the reply can cancel it.
+        let timer = timeout.map(|timeout| delay_to(self, requester, AskTimedOut(id), timeout));
+        self.send_to(to, request(ReplyTo { addr: requester, id, timer, _reply: PhantomData }));
+
+        id
+    }
+
+    /// Send a request to the actor at `to`, and run the continuation once with its reply,
+    /// or with an error if a timeout is given and it expires first.
+    pub fn ask_then<T: 'static, Req: 'static, C>(&mut self, to: Addr, request: impl FnOnce(ReplyTo<T>) -> Req, timeout: Option<Duration>, continuation: C) -> RequestId
+        where C: FnOnce(Result<&T, AskTimedOut>, &mut SystemInterface) + 'static {
+        let id = self.next_request_id();
+
+        // A single-use actor receives the reply, which cancels the timeout, or else the timeout.
+        let requester = self.create_actor(ActorBuilder::new(Some(continuation))
+            .with_handler(move |continuation, reply: &Reply<T>, outbox| {
+                if let Some(continuation) = continuation.take() {
+                    continuation(Ok(&reply.value), outbox);
+                }
+                End
+            })
+            .with_handler(move |continuation, timed_out: &AskTimedOut, outbox| {
+                if let Some(continuation) = continuation.take() {
+                    continuation(Err(*timed_out), outbox);
+                }
+                End
+            })
+            .build());
+
+        let timer = timeout.map(|timeout| delay_to(self, requester, AskTimedOut(id), timeout));
+        self.send_to(to, request(ReplyTo { addr: requester, id, timer, _reply: PhantomData }));
+
+        id
+    }
+
+    fn next_request_id(&mut self) -> RequestId {
+        self.next_request_id += 1;
+        RequestId(self.next_request_id - 1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::mpsc::channel;
+    use std::time::Duration;
+
+    use crate::actors::{ActorBuilder, System};
+    use crate::actors::Fate::Keep;
+    use crate::ask::{AskTimedOut, Reply, ReplyTo, RequestId};
+    use crate::testing::test_system;
+
+    struct Double(i32, ReplyTo<i32>);
+
+    struct Start;
+
+    #[test]
+    fn ask_and_reply() {
+        let mut system = System::new();
+
+        let doubler = system.create_actor(ActorBuilder::new(())
+            .with_handler(|_, Double(i, reply_to), outbox| {
+                reply_to.reply(outbox, i * 2);
+                Keep
+            }).build());
+
+        let (tx, rx) = channel();
+        let then_tx = tx.clone();
+
+        system.create_actor(ActorBuilder::new(None)
+            .with_handler(move |asked, _: &Start, outbox| {
+                *asked = Some(outbox.ask(doubler, |reply_to| Double(21, reply_to), None));
+                Keep
+            })
+            .with_handler(move |asked, reply: &Reply<i32>, _| {
+                assert_eq!(Some(reply.id), *asked);
+                tx.send(reply.value).unwrap();
+                Keep
+            }).build());
+
+        system.send(Start);
+        system.input_interface.ask_then(doubler, |reply_to| Double(5, reply_to), None, move |reply, _| {
+            then_tx.send(*reply.unwrap()).unwrap();
+        });
+
+        while system.handle_one() {};
+
+        assert_eq!(rx.try_iter().collect::<Vec<_>>(), vec![10, 42]);
+    }
+
+    #[test]
+    fn ask_timeout() {
+        let (mut system, mut ticks) = test_system(Duration::from_millis(1));
+
+        let silent = system.create_actor(ActorBuilder::new(())
+            .with_handler(|_, _: &Double, _| Keep).build());
+
+        let (tx, rx) = channel();
+
+        system.input_interface.ask_then(silent, |reply_to| Double(1, reply_to), Some(Duration::from_millis(50)), move |reply: Result<&i32, AskTimedOut>, _| {
+            tx.send(reply.is_err()).unwrap();
+        });
+
+        let start = system.input_interface.now();
+
+        while rx.try_recv().map(|timed_out| assert!(timed_out)).is_err() {
+            ticks.run(&mut system, 1);
+
+            assert!(system.input_interface.now().duration_since(start) < Duration::from_secs(1));
+        }
+    }
+
+    #[test]
+    fn replying_cancels_the_timeout() {
+        let (mut system, mut ticks) = test_system(Duration::from_millis(1));
+
+        let doubler = system.create_actor(ActorBuilder::new(())
+            .with_handler(|_, Double(i, reply_to), outbox| {
+                reply_to.reply(outbox, i * 2);
+                Keep
+            }).build());
+
+        let (tx, rx) = channel();
+        let timed_out_tx = tx.clone();
+
+        system.create_actor(ActorBuilder::new(())
+            .with_handler(move |_, _: &Start, outbox| {
+                // Counted per system, so the first request of this one is 0 whatever other tests did.
+                assert_eq!(outbox.ask(doubler, |reply_to| Double(1, reply_to), Some(Duration::from_millis(50))), RequestId(0));
+                Keep
+            })
+            .with_handler(move |_, reply: &Reply<i32>, _| {
+                tx.send(Ok(reply.value)).unwrap();
+                Keep
+            })
+            .with_handler(move |_, timed_out: &AskTimedOut, _| {
+                timed_out_tx.send(Err(*timed_out)).unwrap();
+                Keep
+            }).build());
+
+        system.send(Start);
+
+        ticks.run(&mut system, 100);
+
+        assert_eq!(rx.try_iter().collect::<Vec<_>>(), vec![Ok(2)]);
+    }
+}
@@ -0,0 +1,309 @@
+use std::cell::Cell;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+use crate::replay::Recordable;
+
+/// Where the time of a System comes from.
+pub trait Clock {
+    fn now(&mut self) -> Instant;
+
+    /// Called every time the message queue of the system runs empty.
+    fn tick(&mut self) {}
+}
+
+/// Wall-clock time.
+pub struct RealClock;
+
+impl Clock for RealClock {
+    fn now(&mut self) -> Instant {
+        Instant::now()
+    }
+}
+
+/// Time that advances by a fixed step every time the message queue runs empty,
+/// no matter how long handling the messages took.
+pub struct SimulationClock {
+    now: Instant,
+    step: Duration,
+}
+
+impl SimulationClock {
+    pub fn new(step: Duration) -> Self {
+        Self { now: Instant::now(), step }
+    }
+}
+
+impl Clock for SimulationClock {
+    fn now(&mut self) -> Instant {
+        self.now
+    }
+
+    fn tick(&mut self) {
+        self.now += self.step;
+    }
+}
+
+/// Time that only advances when told to, for tests. Clones share the same time,
+/// so a test can keep one to advance the one given to the system.
+#[derive(Clone)]
+pub struct ManualClock(Rc<Cell<Instant>>);
+
+impl ManualClock {
+    pub fn new() -> Self {
+        Self(Rc::new(Cell::new(Instant::now())))
+    }
+
+    pub fn advance(&self, by: Duration) {
+        self.0.set(self.0.get() + by);
+    }
+}
+
+impl Default for ManualClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&mut self) -> Instant {
+        self.0.get()
+    }
+}
+
+/// Sent from outside the system once per step of the simulation, as cut by a FixedTimestep.
+#[derive(Clone, Copy, Debug)]
+pub struct Tick {
+    /// Game time that passed in this step.
+    pub dt: Duration,
+    /// Counts the steps, from zero.
+    pub number: u64,
+}
+
+impl Recordable for Tick {
+    fn encode(&self) -> String {
+        format!("{} {}", self.number, self.dt.as_nanos())
+    }
+
+    fn decode(text: &str) -> Option<Self> {
+        let (number, dt) = text.split_once(' ')?;
+        Some(Tick {
+            dt: Duration::from_nanos(dt.parse().ok()?),
+            number: number.parse().ok()?,
+        })
+    }
+}
+
+/// Cuts game time into Ticks of a fixed length, however long the frames in between take,
+/// so that the simulation runs the same at any frame rate.
+pub struct FixedTimestep {
+    dt: Duration,
+    /// Time that has passed but has not been ticked yet.
+    accumulated: Duration,
+    next: u64,
+}
+
+/// When frames take longer than this many steps, the simulation slows down rather than
+/// falling further and further behind.
+const MAX_STEPS_PER_ADVANCE: u32 = 10;
+
+impl FixedTimestep {
+    pub fn new(dt: Duration) -> Self {
+        assert!(dt > Duration::ZERO, "Steps must take some time.");
+        Self { dt, accumulated: Duration::ZERO, next: 0 }
+    }
+
+    /// Let the given time pass, returning the Ticks it adds up to.
+    pub fn advance(&mut self, elapsed: Duration) -> Vec<Tick> {
+        self.accumulated = (self.accumulated + elapsed).min(self.dt * MAX_STEPS_PER_ADVANCE);
+
+        let mut ticks = vec![];
+        while self.accumulated >= self.dt {
+            self.accumulated -= self.dt;
+            ticks.push(self.step());
+        }
+        ticks
+    }
+
+    /// The next Tick, whether its time has come or not.
+    pub fn step(&mut self) -> Tick {
+        let tick = Tick { dt: self.dt, number: self.next };
+        self.next += 1;
+        tick
+    }
+
+    /// How far into the next step time is, from 0 to 1; for rendering in between the last two.
+    pub fn alpha(&self) -> f64 {
+        self.accumulated.as_secs_f64() / self.dt.as_secs_f64()
+    }
+}
+
+/// Game time as derived from a Clock: it stands still while paused, and runs at a multiple of the clock otherwise.
+pub(crate) struct Timeline {
+    clock: Box<dyn Clock>,
+    last_reading: Instant,
+    now: Instant,
+    /// Game time that passed before the last change of pace, not yet added to now.
+    carried: Duration,
+    paused: bool,
+    time_scale: f64,
+}
+
+impl Timeline {
+    pub(crate) fn new(mut clock: Box<dyn Clock>) -> Self {
+        let now = clock.now();
+        Self {
+            clock,
+            last_reading: now,
+            now,
+            carried: Duration::ZERO,
+            paused: false,
+            time_scale: 1.0,
+        }
+    }
+
+    /// Read the clock, and advance game time by however much it moved since the last reading.
+    pub(crate) fn sample(&mut self) -> Instant {
+        let elapsed = self.elapsed();
+        self.now += std::mem::take(&mut self.carried) + elapsed;
+        self.now
+    }
+
+    /// Game time passed since the last reading of the clock.
+    fn elapsed(&mut self) -> Duration {
+        let reading = self.clock.now();
+        let elapsed = reading.saturating_duration_since(self.last_reading);
+        self.last_reading = reading;
+
+        if self.paused { Duration::ZERO } else { elapsed.mul_f64(self.time_scale) }
+    }
+
+    /// Jump to the given game time, as when replaying a recording.
+    pub(crate) fn set(&mut self, now: Instant) {
+        self.last_reading = self.clock.now();
+        self.carried = Duration::ZERO;
+        self.now = now;
+    }
+
+    pub(crate) fn now(&self) -> Instant {
+        self.now
+    }
+
+    pub(crate) fn tick(&mut self) {
+        self.clock.tick();
+    }
+
+    pub(crate) fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    /// Game time only moves on when sampled, but the time up to the change still counts at the old pace.
+    pub(crate) fn set_paused(&mut self, paused: bool) {
+        let elapsed = self.elapsed();
+        self.carried += elapsed;
+        self.paused = paused;
+    }
+
+    pub(crate) fn set_time_scale(&mut self, time_scale: f64) {
+        assert!(time_scale >= 0.0, "Time cannot run backwards.");
+        let elapsed = self.elapsed();
+        self.carried += elapsed;
+        self.time_scale = time_scale;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::mpsc::channel;
+    use std::time::Duration;
+
+    use crate::actors::{ActorBuilder, System};
+    use crate::actors::Fate::Keep;
+    use crate::clock::{FixedTimestep, ManualClock, SimulationClock, Tick};
+
+    #[test]
+    fn pause_and_time_scale() {
+        let mut system = System::new();
+
+        let clock = ManualClock::new();
+        system.set_clock(clock.clone());
+
+        let mut timestep = FixedTimestep::new(Duration::from_secs(1));
+
+        let start = system.input_interface.now();
+        let elapsed = |system: &System| system.input_interface.now().duration_since(start);
+
+        clock.advance(Duration::from_secs(1));
+        system.send(timestep.step());
+        assert_eq!(elapsed(&system), Duration::from_secs(1));
+
+        system.input_interface.pause();
+        clock.advance(Duration::from_secs(1));
+        system.send(timestep.step());
+        assert_eq!(elapsed(&system), Duration::from_secs(1));
+
+        system.input_interface.resume();
+        system.input_interface.set_time_scale(2.0);
+        clock.advance(Duration::from_secs(1));
+        system.send(timestep.step());
+        assert_eq!(elapsed(&system), Duration::from_secs(3));
+    }
+
+    #[test]
+    fn simulation_clock_steps_per_tick() {
+        let mut system = System::new();
+        system.set_clock(SimulationClock::new(Duration::from_millis(10)));
+
+        let mut timestep = FixedTimestep::new(Duration::from_millis(10));
+
+        let start = system.input_interface.now();
+
+        for _ in 0..3 {
+            system.send(timestep.step());
+            while system.handle_one() {}
+        }
+        system.send(timestep.step());
+
+        assert_eq!(system.input_interface.now().duration_since(start), Duration::from_millis(30));
+    }
+
+    #[test]
+    fn messages_are_stamped_with_game_time() {
+        let mut system = System::new();
+
+        let clock = ManualClock::new();
+        system.set_clock(clock.clone());
+
+        let mut timestep = FixedTimestep::new(Duration::from_secs(1));
+
+        let (tx, rx) = channel();
+        system.create_actor(ActorBuilder::new(()).with_handler(move |_, _: &Tick, outbox| {
+            tx.send(outbox.current_message().unwrap().sent_at == outbox.now()).unwrap();
+            Keep
+        }).build());
+
+        system.input_interface.pause();
+        for _ in 0..2 {
+            clock.advance(Duration::from_secs(1));
+            system.send(timestep.step());
+            while system.handle_one() {}
+        }
+
+        assert_eq!(rx.try_iter().collect::<Vec<_>>(), vec![true, true]);
+    }
+
+    #[test]
+    fn fixed_timestep_carries_the_remainder() {
+        let mut timestep = FixedTimestep::new(Duration::from_millis(10));
+
+        assert!(timestep.advance(Duration::from_millis(4)).is_empty());
+        assert!((timestep.alpha() - 0.4).abs() < 1e-9);
+
+        let ticks = timestep.advance(Duration::from_millis(21));
+        assert_eq!(ticks.iter().map(|tick| tick.number).collect::<Vec<_>>(), vec![0, 1]);
+        assert!((timestep.alpha() - 0.5).abs() < 1e-9);
+
+        // A long hitch is cut short.
+        assert_eq!(timestep.advance(Duration::from_secs(10)).len(), 10);
+    }
+}
@@ -0,0 +1,378 @@
+use std::any::Any;
+use std::collections::{HashMap, HashSet};
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+use rand::{Rng, SeedableRng};
+use rand::rngs::StdRng;
+
+use crate::actors::{ActorBuilder, Addr, Envelope, MessageId, System, SystemInterface};
+use crate::actors::Fate::Keep;
+use crate::timer_wheel::TimerWheel;
+use crate::clock::Tick;
+
+/// How precisely timers go off: a delayed message may be up to this much late.
+const RESOLUTION: Duration = Duration::from_millis(1);
+
+/// Refers to a delayed message that has not been delivered yet, to cancel or reschedule it.
+#[derive(Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
+pub struct TimerHandle(u64);
+
+/// Have the delay handler send a message once the given time has passed.
+pub fn delay_from_now<T: Any + 'static + Clone>(outbox: &mut SystemInterface, ping: T, delay: Duration) -> TimerHandle {
+    let handle = next_handle(outbox);
+    outbox.send(DelayUntil(handle, delay, Rc::new(move || Envelope::new(ping.clone()))));
+    handle
+}
+
+/// Like delay_from_now, but the message is only delivered to the actor at the given address.
+pub fn delay_to<T: Any + 'static + Clone>(outbox: &mut SystemInterface, addr: Addr, ping: T, delay: Duration) -> TimerHandle {
+    let handle = next_handle(outbox);
+    delay_to_with_handle(outbox, handle, addr, ping, delay);
+    handle
+}
+
+/// Like delay_to, with a handle taken out beforehand, for when the recipient has to know it.
+pub(crate) fn delay_to_with_handle<T: Any + 'static + Clone>(outbox: &mut SystemInterface, handle: TimerHandle, addr: Addr, ping: T, delay: Duration) {
+    outbox.send(DelayUntil(handle, delay, Rc::new(move || Envelope::to(addr, ping.clone()))));
+}
+
+/// Have the delay handler send a message over and over, until cancelled or until the schedule runs out.
+pub fn repeat<T: Any + 'static + Clone>(outbox: &mut SystemInterface, ping: T, schedule: Schedule) -> TimerHandle {
+    let handle = next_handle(outbox);
+    outbox.send(RepeatOn(handle, schedule, Rc::new(move || Envelope::new(ping.clone()))));
+    handle
+}
+
+/// Like repeat, but the message is only delivered to the actor at the given address.
+pub fn repeat_to<T: Any + 'static + Clone>(outbox: &mut SystemInterface, addr: Addr, ping: T, schedule: Schedule) -> TimerHandle {
+    let handle = next_handle(outbox);
+    outbox.send(RepeatOn(handle, schedule, Rc::new(move || Envelope::to(addr, ping.clone()))));
+    handle
+}
+
+pub(crate) fn next_handle(outbox: &mut SystemInterface) -> TimerHandle {
+    outbox.next_timer += 1;
+    TimerHandle(outbox.next_timer - 1)
+}
+
+/// Counted from SystemInterface::now when the delay handler receives it.
+pub struct DelayUntil(TimerHandle, Duration, Rc<dyn Fn() -> Envelope>);
+
+/// The first message goes out one period after the delay handler receives this.
+pub struct RepeatOn(TimerHandle, Schedule, Rc<dyn Fn() -> Envelope>);
+
+#[derive(Clone, Copy, Debug)]
+enum Period {
+    Time(Duration),
+    Ticks(u64),
+}
+
+/// When a repeated message goes out.
+#[derive(Clone, Copy, Debug)]
+pub struct Schedule {
+    period: Period,
+    jitter: f64,
+    count: Option<u32>,
+}
+
+impl Schedule {
+    pub fn every(period: Duration) -> Self {
+        Self { period: Period::Time(period), jitter: 0.0, count: None }
+    }
+
+    /// Count the Ticks received by the delay handler rather than time.
+    pub fn every_ticks(ticks: u64) -> Self {
+        assert!(ticks > 0, "A message cannot be repeated more than once per tick.");
+        Self { period: Period::Ticks(ticks), jitter: 0.0, count: None }
+    }
+
+    /// Lengthen or shorten every interval at random, by at most the given fraction of the period.
+    pub fn with_jitter(mut self, fraction: f64) -> Self {
+        assert!((0.0..1.0).contains(&fraction), "Jitter must be a fraction of the period.");
+        self.jitter = fraction;
+        self
+    }
+
+    /// Stop after sending the message this many times.
+    pub fn times(mut self, count: u32) -> Self {
+        self.count = Some(count);
+        self
+    }
+}
+
+/// Drop a delayed or repeated message. Does nothing if it was delivered (for the last time) already.
+#[derive(Clone, Copy, Debug)]
+pub struct CancelTimer(pub TimerHandle);
+
+/// Deliver a delayed message after the given time from now instead of when it was due.
+/// A repeated message carries on with its schedule from then on.
+/// Does nothing if it was delivered (for the last time) already.
+#[derive(Clone, Copy, Debug)]
+pub struct RescheduleTimer(pub TimerHandle, pub Duration);
+
+#[derive(Clone, Copy, PartialEq)]
+enum Due {
+    At(Instant),
+    AfterTick(u64),
+}
+
+struct Timer {
+    due: Due,
+    envelope: Rc<dyn Fn() -> Envelope>,
+    /// The request to delay or repeat the message.
+    cause: MessageId,
+    /// The schedule of a repeated message, with how many more times it goes out.
+    repeat: Option<(Schedule, Option<u32>)>,
+}
+
+struct Timers {
+    /// Time is counted in steps of RESOLUTION since this.
+    start: Instant,
+    /// When each timer is due, including outdated entries for timers that were cancelled or rescheduled.
+    by_time: TimerWheel<(Instant, TimerHandle)>,
+    by_tick: TimerWheel<(u64, TimerHandle)>,
+    pending: HashMap<TimerHandle, Timer>,
+    /// Number of Ticks received.
+    ticks: u64,
+    /// Seeded, so that replays see the same jitter.
+    rng: StdRng,
+}
+
+impl Timers {
+    fn new(start: Instant, seed: u64) -> Self {
+        Self {
+            start,
+            by_time: TimerWheel::new(),
+            by_tick: TimerWheel::new(),
+            pending: HashMap::new(),
+            ticks: 0,
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+
+    fn insert(&mut self, handle: TimerHandle, timer: Timer) {
+        self.schedule(handle, timer.due);
+        self.pending.insert(handle, timer);
+    }
+
+    fn schedule(&mut self, handle: TimerHandle, due: Due) {
+        match due {
+            Due::At(at) => self.by_time.insert(self.step_of(at), (at, handle)),
+            Due::AfterTick(tick) => self.by_tick.insert(tick, (tick, handle)),
+        }
+    }
+
+    fn step_of(&self, at: Instant) -> u64 {
+        (at.saturating_duration_since(self.start).as_nanos() / RESOLUTION.as_nanos()) as u64
+    }
+
+    /// When a repeated message is next due, given when it was due last.
+    fn next_due(&mut self, schedule: &Schedule, last: Due, now: Instant) -> Due {
+        let factor = if schedule.jitter > 0.0 { 1.0 + self.rng.gen_range(-schedule.jitter..schedule.jitter) } else { 1.0 };
+
+        match schedule.period {
+            // Counting from when it was due rather than from now keeps the timer from drifting.
+            Period::Time(period) => match last {
+                Due::At(at) => Due::At(at + period.mul_f64(factor)),
+                Due::AfterTick(_) => Due::At(now + period.mul_f64(factor)),
+            },
+            Period::Ticks(ticks) => Due::AfterTick(self.ticks + ((ticks as f64 * factor).round() as u64).max(1)),
+        }
+    }
+
+    /// The timers that have gone off, in the order they were due.
+    fn pop_expired(&mut self, now: Instant) -> Vec<TimerHandle> {
+        // A step only expires once it has passed as a whole.
+        let mut by_time = match self.step_of(now).checked_sub(1) {
+            Some(passed) => self.by_time.expire(passed),
+            None => vec![],
+        };
+        by_time.sort();
+
+        let by_time = by_time.into_iter().map(|(at, handle)| (Due::At(at), handle));
+        let by_tick = self.by_tick.expire(self.ticks).into_iter().map(|(tick, handle)| (Due::AfterTick(tick), handle));
+
+        // Rescheduling a timer to the same time twice leaves two entries that both match it.
+        let mut expired = HashSet::new();
+        by_time.chain(by_tick)
+            .filter(|(due, handle)| self.pending.get(handle).is_some_and(|timer| timer.due == *due) && expired.insert(*handle))
+            .map(|(_, handle)| handle)
+            .collect()
+    }
+
+    /// Produce the message of a timer that went off, and schedule it again if it repeats.
+    fn fire(&mut self, handle: TimerHandle, now: Instant) -> Envelope {
+        let mut timer = self.pending.remove(&handle).expect("Fired a timer that is not pending.");
+        let envelope = (timer.envelope)().caused_by(timer.cause);
+
+        if let Some((schedule, remaining)) = timer.repeat {
+            let remaining = remaining.map(|n| n - 1);
+            if remaining != Some(0) {
+                timer.due = self.next_due(&schedule, timer.due, now);
+                timer.repeat = Some((schedule, remaining));
+                self.insert(handle, timer);
+            }
+        }
+
+        envelope
+    }
+}
+
+/// Start the actor that sends delayed and repeated messages, with the seed of the jitter of repeated ones.
+pub fn init_delay_handler(system: &mut System, seed: u64) {
+    let start = system.input_interface.now();
+    system.create_actor(ActorBuilder::new(Timers::new(start, seed))
+        .with_handler(|st, DelayUntil(handle, delay, envelope), outbox| {
+            // The delayed message is caused by the request to delay it, not by the Tick that releases it.
+            let cause = outbox.current_message().expect("Handler called without a message.").id;
+            st.insert(*handle, Timer {
+                due: Due::At(outbox.now() + *delay),
+                envelope: envelope.clone(),
+                cause,
+                repeat: None,
+            });
+            Keep
+        })
+        .with_handler(|st, RepeatOn(handle, schedule, envelope), outbox| {
+            if schedule.count == Some(0) {
+                return Keep;
+            }
+            let cause = outbox.current_message().expect("Handler called without a message.").id;
+            let due = st.next_due(schedule, Due::At(outbox.now()), outbox.now());
+            st.insert(*handle, Timer {
+                due,
+                envelope: envelope.clone(),
+                cause,
+                repeat: Some((*schedule, schedule.count)),
+            });
+            Keep
+        })
+        .with_handler(|st, CancelTimer(handle), _| {
+            st.pending.remove(handle);
+            Keep
+        })
+        .with_handler(|st, RescheduleTimer(handle, delay), outbox| {
+            let due = Due::At(outbox.now() + *delay);
+            if let Some(timer) = st.pending.get_mut(handle) {
+                timer.due = due;
+                st.schedule(*handle, due);
+            }
+            Keep
+        })
+        .with_handler(|st, _: &Tick, outbox| {
+            st.ticks += 1;
+            for handle in st.pop_expired(outbox.now()) {
+                let envelope = st.fire(handle, outbox.now());
+                outbox.send_envelope(envelope);
+            }
+            Keep
+        })
+        .build());
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::delay::{CancelTimer, delay_from_now, repeat, RescheduleTimer, Schedule};
+    use crate::testing::{collect, test_system};
+
+    #[test]
+    fn test_delay() {
+        let (mut system, mut ticks) = test_system(Duration::from_millis(1));
+
+        #[derive(Clone)]
+        struct Ping;
+
+        let rx = collect(&mut system, |_: &Ping, outbox| outbox.now());
+
+        let start = system.input_interface.now();
+
+        let target = Duration::from_secs(1);
+        delay_from_now(&mut system.input_interface, Ping, target);
+        while system.handle_one() {};
+
+        loop {
+            if let Ok(i) = rx.try_recv() {
+                assert!((i.duration_since(start) - target) <= Duration::from_millis(1));
+                break;
+            } else {
+                ticks.run(&mut system, 1);
+
+                assert!(system.input_interface.now().duration_since(start) < Duration::from_secs(2));
+            }
+        }
+    }
+
+    #[test]
+    fn cancel_and_reschedule() {
+        let (mut system, mut ticks) = test_system(Duration::from_millis(1001));
+
+        #[derive(Clone)]
+        struct Ping(&'static str);
+
+        let rx = collect(&mut system, |Ping(name), _| *name);
+
+        let cancelled = delay_from_now(&mut system.input_interface, Ping("cancelled"), Duration::from_secs(1));
+        let postponed = delay_from_now(&mut system.input_interface, Ping("postponed"), Duration::from_secs(1));
+        delay_from_now(&mut system.input_interface, Ping("kept"), Duration::from_secs(2));
+
+        system.send(CancelTimer(cancelled));
+        system.send(RescheduleTimer(postponed, Duration::from_secs(3)));
+
+        ticks.run(&mut system, 4);
+
+        assert_eq!(rx.try_iter().collect::<Vec<_>>(), vec!["kept", "postponed"]);
+    }
+
+    #[test]
+    fn periodic_timers() {
+        let (mut system, mut ticks) = test_system(Duration::from_secs(1));
+
+        #[derive(Clone)]
+        struct Ping(&'static str);
+
+        let rx = collect(&mut system, |Ping(name), _| *name);
+
+        repeat(&mut system.input_interface, Ping("seconds"), Schedule::every(Duration::from_millis(1500)).times(2));
+        let every_two = repeat(&mut system.input_interface, Ping("ticks"), Schedule::every_ticks(2));
+        repeat(&mut system.input_interface, Ping("jitter"), Schedule::every_ticks(10).with_jitter(0.5));
+        while system.handle_one() {};
+
+        let mut received = vec![];
+        for tick in 1..=8 {
+            ticks.run(&mut system, 1);
+            if tick == 6 {
+                system.send(CancelTimer(every_two));
+            }
+            received.extend(rx.try_iter().filter(|name| *name != "jitter").map(|name| (tick, name)));
+        }
+
+        assert_eq!(received, vec![
+            (2, "seconds"), (2, "ticks"),
+            (4, "seconds"), (4, "ticks"),
+            (6, "ticks"),
+        ]);
+    }
+
+    #[test]
+    fn rescheduling_twice_to_the_same_time() {
+        let (mut system, mut ticks) = test_system(Duration::from_secs(1));
+
+        #[derive(Clone)]
+        struct Ping(&'static str);
+
+        let rx = collect(&mut system, |Ping(name), _| *name);
+
+        let twice = delay_from_now(&mut system.input_interface, Ping("twice"), Duration::from_secs(1));
+        system.send(RescheduleTimer(twice, Duration::from_secs(2)));
+        system.send(RescheduleTimer(twice, Duration::from_secs(2)));
+        delay_from_now(&mut system.input_interface, Ping("later"), Duration::from_secs(3));
+
+        ticks.run(&mut system, 5);
+
+        // Sent once, and the delay handler is still around for the next one.
+        assert_eq!(rx.try_iter().collect::<Vec<_>>(), vec!["twice", "later"]);
+    }
+}
@@ -0,0 +1,128 @@
+use std::marker::PhantomData;
+use std::time::Duration;
+
+use crate::actors::{ActorBuilder, SystemInterface};
+use crate::actors::Fate::{End, Keep};
+use crate::delay::{CancelTimer, delay_to_with_handle, next_handle};
+
+/// Tells apart the expectations of an actor.
+#[derive(Clone, Copy, Eq, PartialEq, Hash, Debug)]
+pub struct Expectation(u64);
+
+/// Delivered to an actor when no message of type M matching what it expected was sent in time.
+pub struct TimedOut<M> {
+    pub expectation: Expectation,
+    _message: PhantomData<fn() -> M>,
+}
+
+#[derive(Clone)]
+struct ExpectationExpired;
+
+impl SystemInterface {
+    /// Expect a broadcast of type M that matches the predicate within the given time, and have
+    /// a `TimedOut<M>` carrying the returned id delivered to the current actor if none is.
+    ///
+    /// Must be called from within a handler, and needs the delay handler to be running.
+    pub fn expect<M: 'static>(&mut self, predicate: impl Fn(&M) -> bool + 'static, within: Duration) -> Expectation {
+        let expecting = self.current_actor().expect("expect called outside of a handler.");
+        let expectation = Expectation(self.next_expectation);
+        self.next_expectation += 1;
+
+        // A single-use actor watches for the message until it arrives, which cancels the timeout, or the timeout does.
+        let timer = next_handle(self);
+        let watcher = self.create_actor(ActorBuilder::new(())
+            .with_handler(move |_, msg: &M, outbox| {
+                if predicate(msg) {
+                    outbox.send(CancelTimer(timer));
+                    End
+                } else {
+                    Keep
+                }
+            })
+            .with_handler(move |_, _: &ExpectationExpired, outbox| {
+                outbox.send_to(expecting, TimedOut::<M> { expectation, _message: PhantomData });
+                End
+            })
+            .build());
+
+        delay_to_with_handle(self, timer, watcher, ExpectationExpired, within);
+
+        expectation
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::any::type_name;
+    use std::sync::mpsc::channel;
+    use std::time::Duration;
+
+    use crate::actors::ActorBuilder;
+    use crate::actors::Fate::Keep;
+    use crate::expect::TimedOut;
+    use crate::testing::test_system;
+
+    struct Wait(u32);
+
+    struct Pong(u32);
+
+    #[test]
+    fn times_out_unless_a_match_arrives() {
+        let (mut system, mut ticks) = test_system(Duration::from_millis(1));
+
+        let (tx, rx) = channel();
+
+        system.create_actor(ActorBuilder::new(vec![])
+            .with_handler(|expecting, Wait(i), outbox| {
+                let i = *i;
+                expecting.push((outbox.expect(move |Pong(j)| *j == i, Duration::from_millis(50)), i));
+                Keep
+            })
+            .with_handler(|_, _: &Pong, _| Keep)
+            .with_handler(move |expecting, timed_out: &TimedOut<Pong>, _| {
+                let (_, i) = expecting.iter().find(|(expectation, _)| *expectation == timed_out.expectation).unwrap();
+                tx.send(*i).unwrap();
+                Keep
+            }).build());
+
+        system.send(Wait(1));
+        system.send(Wait(2));
+        while system.handle_one() {};
+
+        system.send(Pong(2));
+        system.send(Pong(3));
+
+        ticks.run(&mut system, 100);
+
+        assert_eq!(rx.try_iter().collect::<Vec<_>>(), vec![1]);
+    }
+
+    #[test]
+    fn stops_watching_once_a_match_arrives() {
+        let (mut system, mut ticks) = test_system(Duration::from_millis(1));
+
+        let (tx, rx) = channel();
+
+        system.create_actor(ActorBuilder::new(())
+            .with_handler(|_, _: &Wait, outbox| {
+                outbox.expect(|Pong(j)| *j == 1, Duration::from_millis(50));
+                Keep
+            })
+            .with_handler(move |_, _: &TimedOut<Pong>, _| {
+                tx.send(()).unwrap();
+                Keep
+            }).build());
+
+        system.send(Wait(1));
+        while system.handle_one() {};
+
+        // Only the watcher hears Pongs, so once it is gone they go undelivered.
+        system.send(Pong(1));
+        system.send(Pong(2));
+
+        ticks.run(&mut system, 100);
+
+        assert!(rx.try_recv().is_err());
+        assert_eq!(system.dead_letter_counts().get(type_name::<Pong>()), Some(&1));
+    }
+}
@@ -0,0 +1,17 @@
+//! An actor system with a delay scheduler, for other projects to build on; the game in this
+//! repository is one of them.
+//!
+//! The actor system lives in `actors`, and the modules next to it add to it. None of them
+//! need any features; `testing` only adds the setup shared by tests.
+
+pub mod actors;
+pub mod ask;
+pub mod clock;
+pub mod delay;
+pub mod expect;
+pub mod parallel;
+pub mod replay;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
+pub mod timer_wheel;
+pub mod trace;
@@ -0,0 +1,345 @@
+use std::any::{Any, type_name, TypeId};
+use std::collections::HashMap;
+use std::ops::DerefMut;
+use std::panic::{self, AssertUnwindSafe};
+
+use slotmap::SecondaryMap;
+
+use crate::actors::{Addr, Envelope, Fate, panic_message, StateKey};
+
+type SendHandler = dyn Fn(&mut dyn Any, &dyn Any, &mut SendOutbox) -> Fate + Send;
+
+/// Like ActorBuilder, but for actors whose state and handlers are Send, so that
+/// System::handle_batch can run them on worker threads.
+///
+/// Their handlers only see a SendOutbox, which can send Send messages and nothing else.
+pub struct SendActorBuilder<S> {
+    init_state: S,
+    handlers: Vec<(TypeId, &'static str, Box<SendHandler>)>,
+}
+
+pub struct SendActorData {
+    init_state: Box<dyn Any + Send>,
+    handlers: Vec<(TypeId, &'static str, Box<SendHandler>)>,
+}
+
+impl<S: Send + 'static> SendActorBuilder<S> {
+    pub fn new(init_state: S) -> Self {
+        Self {
+            init_state,
+            handlers: vec![],
+        }
+    }
+
+    /// Messages of type M are read by worker threads and the main thread at the same time, hence Sync.
+    ///
+    /// Returning Fate::Become is not supported, and counts as a failure of the actor.
+    pub fn with_handler<M, F>(mut self, handler: F) -> Self
+        where M: Send + Sync + 'static,
+              F: Fn(&mut S, &M, &mut SendOutbox) -> Fate + Send + 'static {
+        self.handlers.push((TypeId::of::<M>(), type_name::<M>(), Box::new(move |state: &mut dyn Any, message: &dyn Any, outbox: &mut SendOutbox| {
+            let state = state.downcast_mut::<S>().expect("Wrong state type!");
+            let message = message.downcast_ref::<M>().expect("Wrong message type!");
+            handler(state, message, outbox)
+        })));
+        self
+    }
+
+    pub fn build(self) -> SendActorData {
+        SendActorData {
+            init_state: Box::new(self.init_state),
+            handlers: self.handlers,
+        }
+    }
+}
+
+/// The messages sent by the handler of a Send actor, to be queued once it is done.
+pub struct SendOutbox {
+    actor: Addr,
+    sent: Vec<SendEnvelope>,
+}
+
+impl SendOutbox {
+    pub fn send<Msg: Send + 'static>(&mut self, msg: Msg) {
+        self.sent.push(SendEnvelope {
+            recipient: None,
+            type_name: type_name::<Msg>(),
+            message: Box::new(msg),
+        });
+    }
+
+    pub fn send_to<Msg: Send + 'static>(&mut self, addr: Addr, msg: Msg) {
+        self.sent.push(SendEnvelope {
+            recipient: Some(addr),
+            type_name: type_name::<Msg>(),
+            message: Box::new(msg),
+        });
+    }
+
+    /// Address of the actor whose handler is running.
+    pub fn current_actor(&self) -> Addr {
+        self.actor
+    }
+}
+
+/// An Envelope that may be handed back from a worker thread.
+pub(crate) struct SendEnvelope {
+    recipient: Option<Addr>,
+    type_name: &'static str,
+    message: Box<dyn Any + Send>,
+}
+
+impl From<SendEnvelope> for Envelope {
+    fn from(envelope: SendEnvelope) -> Self {
+        Envelope::from_parts(envelope.recipient, envelope.type_name, envelope.message)
+    }
+}
+
+pub(crate) struct SendActor {
+    state: Box<dyn Any + Send>,
+    handlers: Vec<(TypeId, &'static str, Box<SendHandler>)>,
+}
+
+/// A message that worker threads may read, shared by reference.
+#[derive(Clone, Copy)]
+pub(crate) struct SharedMessage<'a>(&'a dyn Any);
+
+// SAFETY: a SharedMessage is only created for messages that a Send actor has a handler for,
+// and SendActorBuilder::with_handler requires those to be Sync. They are never mutated while shared.
+unsafe impl Send for SharedMessage<'_> {}
+
+impl<'a> SharedMessage<'a> {
+    /// The caller must make sure that some Send actor handles messages of this type.
+    pub(crate) fn new(msg: &'a dyn Any) -> Self {
+        Self(msg)
+    }
+}
+
+/// What happened while a Send actor handled its share of a batch.
+pub(crate) struct SendRun {
+    /// The envelopes sent, with the index of the message that caused them.
+    pub(crate) sent: Vec<(usize, Vec<SendEnvelope>)>,
+    /// Set if the actor ended: to None if it did so normally, and otherwise
+    /// to the type of the message it failed on and the reason.
+    pub(crate) ended: Option<Option<(&'static str, String)>>,
+}
+
+impl SendActor {
+    /// Feed messages to the actor in order, stopping as soon as it ends or fails.
+    pub(crate) fn run<'a>(&mut self, actor: Addr, mail: impl IntoIterator<Item=(usize, SharedMessage<'a>)>) -> SendRun {
+        let mut run = SendRun { sent: vec![], ended: None };
+
+        'mail: for (index, SharedMessage(msg)) in mail {
+            let mut outbox = SendOutbox { actor, sent: vec![] };
+
+            for (typ, message_type, handler) in &self.handlers {
+                if *typ != msg.type_id() {
+                    continue;
+                }
+
+                let state = self.state.deref_mut();
+                let outcome = panic::catch_unwind(AssertUnwindSafe(|| handler(state, msg, &mut outbox)));
+
+                let ended = match outcome {
+                    Ok(Fate::Keep) => continue,
+                    Ok(Fate::End) => None,
+                    Ok(Fate::Become(_)) => Some((*message_type, "Send actors cannot switch behavior.".to_string())),
+                    Err(payload) => Some((*message_type, panic_message(payload))),
+                };
+
+                run.sent.push((index, outbox.sent));
+                run.ended = Some(ended);
+                break 'mail;
+            }
+
+            run.sent.push((index, outbox.sent));
+        }
+
+        run
+    }
+}
+
+/// The Send actors of a System, with an index of which ones handle which message types.
+#[derive(Default)]
+pub(crate) struct SendActors {
+    actors: SecondaryMap<StateKey, SendActor>,
+    subscribers: HashMap<TypeId, Vec<StateKey>>,
+}
+
+impl SendActors {
+    pub(crate) fn insert(&mut self, state_key: StateKey, actor_data: SendActorData) {
+        for (typ, _, _) in &actor_data.handlers {
+            let subscribed = self.subscribers.entry(*typ).or_default();
+            if !subscribed.contains(&state_key) {
+                subscribed.push(state_key);
+            }
+        }
+
+        self.actors.insert(state_key, SendActor {
+            state: actor_data.init_state,
+            handlers: actor_data.handlers,
+        });
+    }
+
+    /// Remove an actor for good, returning whether it was there.
+    pub(crate) fn remove(&mut self, state_key: StateKey) -> bool {
+        match self.actors.remove(state_key) {
+            Some(corpse) => {
+                for (typ, _, _) in corpse.handlers {
+                    if let Some(subscribed) = self.subscribers.get_mut(&typ) {
+                        subscribed.retain(|k| *k != state_key);
+                        if subscribed.is_empty() {
+                            self.subscribers.remove(&typ);
+                        }
+                    }
+                }
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// The Send actors that should receive a message, in a stable order.
+    pub(crate) fn recipients(&self, msg: &dyn Any, recipient: Option<Addr>) -> Vec<StateKey> {
+        let typ = msg.type_id();
+
+        match recipient {
+            None => self.subscribers.get(&typ).cloned().unwrap_or_default(),
+            Some(Addr(state_key)) => self.actors.get(state_key)
+                .filter(|actor| actor.handlers.iter().any(|(t, _, _)| *t == typ))
+                .map(|_| vec![state_key])
+                .unwrap_or_default(),
+        }
+    }
+
+    /// Take an actor out to run it elsewhere; its subscriptions stay in place until it is put back.
+    pub(crate) fn take(&mut self, state_key: StateKey) -> SendActor {
+        self.actors.remove(state_key).expect("Send actor taken twice.")
+    }
+
+    pub(crate) fn put_back(&mut self, state_key: StateKey, actor: SendActor) {
+        self.actors.insert(state_key, actor);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::mpsc::channel;
+    use std::thread;
+
+    use crate::actors::{ActorBuilder, ActorFailed, Addr, System};
+    use crate::actors::Fate::Keep;
+    use crate::parallel::SendActorBuilder;
+
+    struct Step(u32);
+
+    struct Stepped(Addr, u32, u32);
+
+    /// Run the same counters once with handle_one and once with handle_batch, and compare what they sent.
+    fn counters(batch: bool) -> Vec<(Addr, u32, u32)> {
+        let mut system = System::new();
+        system.set_strict(true);
+
+        let (tx, rx) = channel();
+
+        system.create_actor(ActorBuilder::new(())
+            .with_handler(move |_, Stepped(addr, step, total), _| {
+                tx.send((*addr, *step, *total)).unwrap();
+                Keep
+            }).build());
+
+        let counters: Vec<Addr> = (0..16).map(|_| system.create_send_actor(SendActorBuilder::new(0)
+            .with_handler(|total, Step(step), outbox| {
+                *total += step;
+                outbox.send(Stepped(outbox.current_actor(), *step, *total));
+                Keep
+            }).build())).collect();
+
+        for step in 1..=5 {
+            system.send(Step(step));
+            system.send_to(counters[step as usize], Step(100));
+        }
+
+        if batch {
+            while system.handle_batch(4) {}
+        } else {
+            while system.handle_one() {}
+        }
+
+        rx.try_iter().collect()
+    }
+
+    #[test]
+    fn batch_preserves_order() {
+        let batched = counters(true);
+
+        assert_eq!(batched.len(), 16 * 5 + 5);
+
+        // Every counter saw its messages in the order they were sent.
+        let first = batched[0].0;
+        let of_first: Vec<_> = batched.iter().filter(|(addr, _, _)| *addr == first).map(|(_, _, total)| *total).collect();
+        assert_eq!(of_first, vec![1, 3, 6, 10, 15]);
+
+        let mut sequential = counters(false);
+        let mut batched = batched;
+        sequential.sort_by_key(|(addr, step, total)| (addr.to_string(), *step, *total));
+        batched.sort_by_key(|(addr, step, total)| (addr.to_string(), *step, *total));
+        assert_eq!(sequential, batched);
+    }
+
+    #[test]
+    fn send_actors_run_off_the_main_thread() {
+        struct Where;
+
+        let mut system = System::new();
+
+        let (tx, rx) = channel();
+
+        system.create_send_actor(SendActorBuilder::new(tx)
+            .with_handler(|tx, _: &Where, _| {
+                tx.send(thread::current().id()).unwrap();
+                Keep
+            }).build());
+
+        system.send(Where);
+        while system.handle_batch(2) {}
+        system.send(Where);
+        while system.handle_one() {}
+
+        assert_ne!(rx.recv().unwrap(), thread::current().id());
+        assert_eq!(rx.recv().unwrap(), thread::current().id());
+    }
+
+    #[test]
+    fn failing_send_actor_ends() {
+        let mut system = System::new();
+
+        let (tx, rx) = channel();
+
+        system.create_actor(ActorBuilder::new(())
+            .with_handler(move |_, failed: &ActorFailed, _| {
+                tx.send(failed.payload.clone()).unwrap();
+                Keep
+            }).build());
+
+        let fragile = system.create_send_actor(SendActorBuilder::new(())
+            .with_handler(|_, Step(step), _| {
+                if *step == 2 {
+                    panic!("Boom!");
+                }
+                Keep
+            }).build());
+
+        for step in 1..=3 {
+            system.send_to(fragile, Step(step));
+        }
+        while system.handle_batch(2) {}
+
+        assert_eq!(rx.try_iter().collect::<Vec<_>>(), vec!["Boom!".to_string()]);
+
+        system.send_to(fragile, Step(1));
+        while system.handle_batch(2) {}
+
+        assert_eq!(system.dead_letter_counts().len(), 1);
+    }
+}
@@ -0,0 +1,297 @@
+use std::any::{Any, type_name, TypeId};
+use std::collections::{HashMap, VecDeque};
+use std::fmt::Write;
+use std::time::{Duration, Instant};
+
+use slotmap::{Key, KeyData};
+
+use crate::actors::{Addr, Envelope};
+
+/// A message that can be written to a recording, as a single line of text.
+pub trait Recordable: Sized + 'static {
+    fn encode(&self) -> String;
+
+    fn decode(text: &str) -> Option<Self>;
+}
+
+type Encoder = fn(&dyn Any) -> String;
+
+type Decoder = fn(&str) -> Option<Box<dyn Any>>;
+
+/// The message types that make up the input of a session, and how to encode them.
+#[derive(Default)]
+pub struct RecordedTypes {
+    encoders: HashMap<TypeId, (&'static str, Encoder)>,
+    decoders: HashMap<&'static str, Decoder>,
+}
+
+impl RecordedTypes {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with<M: Recordable>(mut self) -> Self {
+        self.encoders.insert(TypeId::of::<M>(), (type_name::<M>(), |msg| {
+            msg.downcast_ref::<M>().expect("Wrong message type!").encode()
+        }));
+        self.decoders.insert(type_name::<M>(), |text| {
+            M::decode(text).map(|msg| Box::new(msg) as Box<dyn Any>)
+        });
+        self
+    }
+
+    pub(crate) fn contains(&self, msg: &dyn Any) -> bool {
+        self.encoders.contains_key(&msg.type_id())
+    }
+
+    fn encode(&self, msg: &dyn Any) -> Option<(&'static str, String)> {
+        self.encoders.get(&msg.type_id()).map(|(type_name, encode)| (*type_name, encode(msg)))
+    }
+
+    fn decode(&self, type_name: &str, text: &str) -> Option<(&'static str, Box<dyn Any>)> {
+        let (type_name, decode) = self.decoders.get_key_value(type_name)?;
+        decode(text).map(|msg| (*type_name, msg))
+    }
+}
+
+/// A message sent from outside the system, relative to the start of the recording.
+pub(crate) struct RecordedSend {
+    elapsed: Duration,
+    /// The message itself, for those of a recorded type. Others are sent by
+    /// the replaying program again, and only their timing is kept.
+    input: Option<RecordedInput>,
+}
+
+pub(crate) struct RecordedInput {
+    recipient: Option<u64>,
+    type_name: String,
+    text: String,
+}
+
+/// Everything needed to play a session back: the RNG seed of the game, and the messages sent from outside the system.
+pub struct Recording {
+    pub seed: u64,
+    sends: Vec<RecordedSend>,
+}
+
+impl Recording {
+    /// One line for the seed, then one per message: the elapsed nanoseconds, and for
+    /// recorded types the recipient (or `-` for broadcasts), type name and encoded message.
+    pub fn to_text(&self) -> String {
+        let mut text = format!("seed {}\n", self.seed);
+
+        for send in &self.sends {
+            write!(text, "{}", send.elapsed.as_nanos()).unwrap();
+            if let Some(input) = &send.input {
+                let recipient = input.recipient.map_or("-".to_string(), |recipient| recipient.to_string());
+                write!(text, " {} {} {}", recipient, input.type_name, input.text).unwrap();
+            }
+            text.push('\n');
+        }
+
+        text
+    }
+
+    pub fn from_text(text: &str) -> Result<Self, String> {
+        let mut lines = text.lines();
+
+        let seed = lines.next()
+            .and_then(|line| line.strip_prefix("seed "))
+            .and_then(|seed| seed.parse().ok())
+            .ok_or("Recording does not start with a seed.")?;
+
+        let sends = lines.enumerate().map(|(i, line)| {
+            let invalid = || format!("Invalid recording entry on line {}.", i + 2);
+            let mut fields = line.splitn(4, ' ');
+
+            let elapsed = fields.next().and_then(|f| f.parse().ok()).map(Duration::from_nanos).ok_or_else(invalid)?;
+
+            let input = match fields.next() {
+                None => None,
+                Some(recipient) => Some(RecordedInput {
+                    recipient: if recipient == "-" { None } else { Some(recipient.parse().map_err(|_| invalid())?) },
+                    type_name: fields.next().ok_or_else(invalid)?.to_string(),
+                    text: fields.next().unwrap_or("").to_string(),
+                }),
+            };
+
+            Ok(RecordedSend { elapsed, input })
+        }).collect::<Result<_, String>>()?;
+
+        Ok(Self { seed, sends })
+    }
+}
+
+/// Recording or replay in progress.
+pub(crate) enum Session {
+    Recording {
+        start: Instant,
+        types: RecordedTypes,
+        recording: Recording,
+    },
+    Replaying {
+        start: Instant,
+        types: RecordedTypes,
+        sends: VecDeque<RecordedSend>,
+    },
+}
+
+impl Session {
+    pub(crate) fn recording(seed: u64, types: RecordedTypes, now: Instant) -> Self {
+        Session::Recording {
+            start: now,
+            types,
+            recording: Recording { seed, sends: vec![] },
+        }
+    }
+
+    pub(crate) fn replaying(recording: Recording, types: RecordedTypes, now: Instant) -> Self {
+        Session::Replaying {
+            start: now,
+            types,
+            sends: recording.sends.into(),
+        }
+    }
+
+    pub(crate) fn into_recording(self) -> Option<Recording> {
+        match self {
+            Session::Recording { recording, .. } => Some(recording),
+            Session::Replaying { .. } => None,
+        }
+    }
+
+    pub(crate) fn is_replaying(&self) -> bool {
+        matches!(self, Session::Replaying { .. })
+    }
+
+    /// Keep track of a message sent from outside the system at the given game time,
+    /// returning the message to actually send and the time it is sent at.
+    ///
+    /// A replay goes through the recording one message at a time, for every message sent by
+    /// the replaying program: those of the recorded types are swapped for the recorded ones.
+    pub(crate) fn pass(&mut self, envelope: Envelope, now: Instant) -> (Envelope, Instant) {
+        match self {
+            Session::Recording { start, types, recording } => {
+                recording.sends.push(RecordedSend {
+                    elapsed: now - *start,
+                    input: types.encode(envelope.message()).map(|(type_name, text)| RecordedInput {
+                        recipient: envelope.recipient().map(|Addr(state_key)| state_key.data().as_ffi()),
+                        type_name: type_name.to_string(),
+                        text,
+                    }),
+                });
+                (envelope, now)
+            }
+            Session::Replaying { start, types, sends } => {
+                let send = sends.pop_front().expect("Replay ran past the end of the recording.");
+                let now = *start + send.elapsed;
+
+                match send.input {
+                    Some(input) if types.contains(envelope.message()) => {
+                        let (type_name, msg) = types.decode(&input.type_name, &input.text)
+                            .unwrap_or_else(|| panic!("Cannot replay a message of type {}.", input.type_name));
+                        let recipient = input.recipient.map(|recipient| Addr(KeyData::from_ffi(recipient).into()));
+                        (Envelope::from_parts(recipient, type_name, msg), now)
+                    }
+                    None if !types.contains(envelope.message()) => (envelope, now),
+                    _ => panic!("Replay diverged from the recording at a message of type {}.", envelope.type_name()),
+                }
+            }
+        }
+    }
+
+    pub(crate) fn is_finished(&self) -> bool {
+        match self {
+            Session::Replaying { sends, .. } => sends.is_empty(),
+            Session::Recording { .. } => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::mpsc::{channel, Receiver};
+    use std::time::Duration;
+
+    use crate::actors::{ActorBuilder, System};
+    use crate::actors::Fate::Keep;
+    use crate::clock::Tick;
+    use crate::delay::delay_from_now;
+    use crate::replay::{Recordable, RecordedTypes, Recording};
+    use crate::testing::test_system;
+
+    struct Input(u32);
+
+    impl Recordable for Input {
+        fn encode(&self) -> String {
+            self.0.to_string()
+        }
+
+        fn decode(text: &str) -> Option<Self> {
+            text.parse().ok().map(Input)
+        }
+    }
+
+    #[derive(Clone)]
+    struct Fired(u32);
+
+    /// Reports the inputs that came back out of the delay handler, and at which tick.
+    fn game(system: &mut System) -> Receiver<(u32, u32)> {
+        let (tx, rx) = channel();
+
+        system.create_actor(ActorBuilder::new(0)
+            .with_handler(|ticks, _: &Tick, _| {
+                *ticks += 1;
+                Keep
+            })
+            .with_handler(|_, Input(i), outbox| {
+                delay_from_now(outbox, Fired(*i), Duration::from_millis(5 * *i as u64));
+                Keep
+            })
+            .with_handler(move |ticks, Fired(i), _| {
+                tx.send((*ticks, *i)).unwrap();
+                Keep
+            }).build());
+
+        rx
+    }
+
+    fn types() -> RecordedTypes {
+        RecordedTypes::new().with::<Tick>().with::<Input>()
+    }
+
+    #[test]
+    fn replay_reproduces_timing() {
+        let (mut system, mut ticks) = test_system(Duration::from_millis(1));
+        let rx = game(&mut system);
+
+        system.start_recording(42, types());
+
+        system.send(Input(1));
+        system.send(Input(3));
+
+        let mut recorded = vec![];
+        while recorded.len() < 2 {
+            ticks.run(&mut system, 1);
+            recorded.extend(rx.try_iter());
+        }
+
+        let recording = Recording::from_text(&system.stop_recording().unwrap().to_text()).unwrap();
+        assert_eq!(recording.seed, 42);
+
+        let (mut system, mut ticks) = test_system(Duration::from_millis(1));
+        let rx = game(&mut system);
+
+        system.start_replay(recording, types());
+
+        // Live input is swapped for the recorded one.
+        system.send(Input(2));
+        system.send(Input(4));
+
+        while system.is_replaying() {
+            ticks.run(&mut system, 1);
+        }
+
+        assert_eq!(rx.try_iter().collect::<Vec<_>>(), recorded);
+    }
+}
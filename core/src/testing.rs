@@ -0,0 +1,52 @@
+//! The setup shared by tests, here and in crates built on this one: a system on a ManualClock
+//! with the delay handler running, driven tick by tick.
+
+use std::sync::mpsc::{channel, Receiver};
+use std::time::Duration;
+
+use crate::actors::{ActorBuilder, System, SystemInterface};
+use crate::actors::Fate::Keep;
+use crate::clock::{FixedTimestep, ManualClock};
+use crate::delay::init_delay_handler;
+
+/// Advances the clock of a test system and sends it its Ticks.
+pub struct Ticks {
+    clock: ManualClock,
+    step: Duration,
+    timestep: FixedTimestep,
+}
+
+impl Ticks {
+    /// Advance the clock by one step, send the Tick and handle everything that follows, this many times.
+    pub fn run(&mut self, system: &mut System, count: usize) {
+        for _ in 0..count {
+            self.clock.advance(self.step);
+            system.send(self.timestep.step());
+            while system.handle_one() {}
+        }
+    }
+}
+
+/// A system on a ManualClock, with the delay handler started with seed 0, and the Ticks of the given length to drive it.
+pub fn test_system(step: Duration) -> (System, Ticks) {
+    let mut system = System::new();
+
+    let clock = ManualClock::new();
+    system.set_clock(clock.clone());
+
+    init_delay_handler(&mut system, 0);
+
+    (system, Ticks { clock, step, timestep: FixedTimestep::new(step) })
+}
+
+/// Start an actor that sends what `f` makes of every M it hears down the returned channel.
+pub fn collect<M: 'static, T: 'static>(system: &mut System, f: impl Fn(&M, &SystemInterface) -> T + 'static) -> Receiver<T> {
+    let (tx, rx) = channel();
+
+    system.create_actor(ActorBuilder::new(()).with_handler(move |_, msg: &M, outbox| {
+        tx.send(f(msg, outbox)).unwrap();
+        Keep
+    }).build());
+
+    rx
+}
@@ -0,0 +1,147 @@
+const SLOT_BITS: u32 = 6;
+const SLOTS: usize = 1 << SLOT_BITS;
+const LEVELS: usize = 4;
+
+/// Holds items until a numbered step comes up, with constant-time insertion and expiry.
+///
+/// The first level has a slot for each of the next 64 steps, the next level a slot for each
+/// of the 64 spans of 64 steps after that, and so on. Whenever a span starts, its slot on the
+/// level above is spread out over the levels below.
+pub struct TimerWheel<T> {
+    /// The next step to expire.
+    current: u64,
+    /// Items with the step they are due at, per slot, per level.
+    levels: Vec<Vec<Vec<(u64, T)>>>,
+    /// Items too far ahead for the highest level.
+    overflow: Vec<(u64, T)>,
+    len: usize,
+}
+
+impl<T> TimerWheel<T> {
+    pub fn new() -> Self {
+        Self {
+            current: 0,
+            levels: (0..LEVELS).map(|_| (0..SLOTS).map(|_| vec![]).collect()).collect(),
+            overflow: vec![],
+            len: 0,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Items due at a step that has expired already go out with the next one.
+    pub fn insert(&mut self, step: u64, item: T) {
+        self.place(step.max(self.current), item);
+        self.len += 1;
+    }
+
+    fn place(&mut self, step: u64, item: T) {
+        let ahead = step - self.current;
+
+        for level in 0..LEVELS {
+            let shift = SLOT_BITS * level as u32;
+            if ahead < 1 << (shift + SLOT_BITS) {
+                self.levels[level][(step >> shift) as usize % SLOTS].push((step, item));
+                return;
+            }
+        }
+
+        self.overflow.push((step, item));
+    }
+
+    /// Remove and return the items due at or before the given step, in the order they are due.
+    pub fn expire(&mut self, up_to: u64) -> Vec<T> {
+        let mut expired = vec![];
+
+        while self.current <= up_to {
+            if self.len == 0 {
+                // Nothing to spread out or expire on the way.
+                self.current = up_to + 1;
+                break;
+            }
+
+            self.cascade();
+
+            let slot = self.current as usize % SLOTS;
+            let due = std::mem::take(&mut self.levels[0][slot]);
+            self.len -= due.len();
+            expired.extend(due.into_iter().map(|(_, item)| item));
+
+            self.current += 1;
+
+            // With nothing left on the first level, skip ahead to the next span.
+            if self.levels[0].iter().all(Vec::is_empty) {
+                let next_span = (self.current + SLOTS as u64 - 1) & !(SLOTS as u64 - 1);
+                self.current = next_span.min(up_to + 1);
+            }
+        }
+
+        expired
+    }
+
+    /// When the current step starts a span of a higher level, spread its slot out over the levels below.
+    fn cascade(&mut self) {
+        let step = self.current;
+
+        if step.is_multiple_of(1 << (SLOT_BITS * LEVELS as u32)) {
+            for (due, item) in std::mem::take(&mut self.overflow) {
+                self.place(due, item);
+            }
+        }
+
+        for level in (1..LEVELS).rev() {
+            let shift = SLOT_BITS * level as u32;
+            if step.is_multiple_of(1 << shift) {
+                let slot = (step >> shift) as usize % SLOTS;
+                for (due, item) in std::mem::take(&mut self.levels[level][slot]) {
+                    self.place(due, item);
+                }
+            }
+        }
+    }
+}
+
+impl<T> Default for TimerWheel<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{Rng, SeedableRng};
+    use rand::rngs::StdRng;
+
+    use crate::timer_wheel::TimerWheel;
+
+    #[test]
+    fn expires_everything_once_and_in_order() {
+        let mut wheel = TimerWheel::new();
+        let mut rng = StdRng::seed_from_u64(1);
+
+        // Some well past the range of the highest level.
+        let dues: Vec<u64> = (0..2000)
+            .map(|i| if i % 100 == 0 { rng.gen_range(0..1 << 25) } else { rng.gen_range(0..300_000) })
+            .collect();
+
+        for (i, due) in dues.iter().enumerate() {
+            wheel.insert(*due, i);
+        }
+
+        let mut now = 0;
+        let mut expired = vec![];
+        while !wheel.is_empty() {
+            now += rng.gen_range(0..5000);
+            let batch: Vec<u64> = wheel.expire(now).into_iter().map(|i| dues[i]).collect();
+            assert!(batch.iter().all(|due| *due <= now && now - *due < 5000));
+            assert!(batch.windows(2).all(|pair| pair[0] <= pair[1]));
+            expired.extend(batch);
+        }
+
+        let mut sorted = dues;
+        sorted.sort_unstable();
+        assert_eq!(expired, sorted);
+    }
+}
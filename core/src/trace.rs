@@ -0,0 +1,145 @@
+use std::collections::HashSet;
+use std::fmt::Write;
+use std::ops::Range;
+
+use crate::actors::{Metadata, MessageId};
+
+/// A record of which message caused which, as collected by System::start_tracing.
+#[derive(Default)]
+pub struct Trace {
+    messages: Vec<Metadata>,
+}
+
+impl Trace {
+    pub(crate) fn record(&mut self, metadata: Metadata) {
+        self.messages.push(metadata);
+    }
+
+    pub fn messages(&self) -> &[Metadata] {
+        &self.messages
+    }
+
+    fn window(&self, ticks: &Range<u64>) -> impl Iterator<Item=&Metadata> {
+        let ticks = ticks.clone();
+        self.messages.iter().filter(move |m| ticks.contains(&m.tick))
+    }
+
+    /// The causal graph of the messages sent during the given ticks, in Graphviz DOT format.
+    ///
+    /// Causes sent before the window are left out, so their effects show up as roots.
+    pub fn to_dot(&self, ticks: Range<u64>) -> String {
+        let ids: HashSet<MessageId> = self.window(&ticks).map(|m| m.id).collect();
+
+        let mut dot = String::from("digraph causality {\n");
+
+        for m in self.window(&ticks) {
+            writeln!(dot, "    m{} [label=\"{}\\ntick {}\"];", m.id, short_type_name(m.type_name), m.tick).unwrap();
+        }
+
+        for m in self.window(&ticks) {
+            if let Some(cause) = m.cause.filter(|cause| ids.contains(cause)) {
+                writeln!(dot, "    m{} -> m{};", cause, m.id).unwrap();
+            }
+        }
+
+        dot.push_str("}\n");
+        dot
+    }
+
+    /// The messages sent during the given ticks as a JSON array, each with the id of its cause.
+    pub fn to_json(&self, ticks: Range<u64>) -> String {
+        let entries: Vec<String> = self.window(&ticks).map(|m| {
+            format!("{{\"id\":{},\"type\":\"{}\",\"tick\":{},\"cause\":{},\"sender\":{},\"recipient\":{}}}",
+                    m.id,
+                    short_type_name(m.type_name),
+                    m.tick,
+                    m.cause.map_or("null".to_string(), |cause| cause.to_string()),
+                    m.sender.map_or("null".to_string(), |addr| format!("\"{}\"", addr)),
+                    m.recipient.map_or("null".to_string(), |addr| format!("\"{}\"", addr)))
+        }).collect();
+
+        format!("[{}]", entries.join(",\n"))
+    }
+}
+
+/// Strip the module paths from a type name, so that `crate::ask::Reply<crate::Foo>` becomes `Reply<Foo>`.
+fn short_type_name(name: &str) -> String {
+    let mut short = String::new();
+    let mut segment = String::new();
+
+    for c in name.chars() {
+        if c.is_alphanumeric() || c == '_' {
+            segment.push(c);
+        } else if c == ':' {
+            segment.clear();
+        } else {
+            short.push_str(&segment);
+            segment.clear();
+            short.push(c);
+        }
+    }
+
+    short.push_str(&segment);
+    short
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::actors::{ActorBuilder, System};
+    use crate::actors::Fate::Keep;
+    use crate::clock::{FixedTimestep, Tick};
+    use crate::trace::short_type_name;
+
+    #[test]
+    fn causal_chain() {
+        struct Ping;
+
+        struct Pong;
+
+        struct Done;
+
+        let mut system = System::new();
+        let mut timestep = FixedTimestep::new(Duration::from_millis(10));
+
+        system.create_actor(ActorBuilder::new(())
+            .with_handler(|_, _: &Tick, _| Keep)
+            .with_handler(|_, _: &Ping, outbox| {
+                outbox.send(Pong);
+                Keep
+            })
+            .with_handler(|_, _: &Pong, outbox| {
+                outbox.send(Done);
+                Keep
+            })
+            .with_handler(|_, _: &Done, _| Keep)
+            .build());
+
+        system.start_tracing();
+
+        for _ in 0..2 {
+            system.send(timestep.step());
+            system.send(Ping);
+            while system.handle_one() {};
+        }
+
+        let trace = system.stop_tracing().unwrap();
+
+        assert_eq!(trace.messages().len(), 8);
+
+        let dot = trace.to_dot(1..2);
+        assert!(dot.contains("m5 [label=\"Ping\\ntick 1\"];"));
+        assert!(dot.contains("m5 -> m6;"));
+        assert!(dot.contains("m6 -> m7;"));
+        assert!(!dot.contains("m1 "));
+
+        assert!(trace.to_json(0..1).contains("{\"id\":2,\"type\":\"Pong\",\"tick\":0,\"cause\":1,"));
+    }
+
+    #[test]
+    fn short_type_names() {
+        assert_eq!(short_type_name("voyagers::ask::Reply<voyagers::ShipId>"), "Reply<ShipId>");
+        assert_eq!(short_type_name("(i32, alloc::string::String)"), "(i32, String)");
+    }
+}
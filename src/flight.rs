@@ -0,0 +1,267 @@
+//! A Newtonian flight model: ships have momentum, and the controls only set how hard
+//! the main engine and the reaction control thrusters push.
+//!
+//! With the physics feature, the forces go to the physics world, which moves the ship.
+//! Without it, the flight model integrates them itself.
+
+use nalgebra::{Isometry3, Vector3};
+#[cfg(not(feature = "physics"))]
+use nalgebra::UnitQuaternion;
+
+use voyagers_core::actors::Fate::Keep;
+use voyagers_core::actors::{ActorBuilder, Keyed, System};
+use voyagers_core::clock::Tick;
+
+use crate::game::{ShipCreated, ShipId, ShipMoved};
+
+/// How a ship handles.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct FlightModel {
+    pub mass: f64,
+    /// Moment of inertia, the same around every axis.
+    pub inertia: f64,
+    /// Force of the main engine at full throttle, pushing the ship forward along its -z axis.
+    pub max_thrust: f64,
+    /// Force the reaction control thrusters can put out in any direction.
+    pub max_rcs_force: f64,
+    /// Torque the reaction control thrusters can put out around any axis.
+    pub max_rcs_torque: f64,
+    /// Radians per second that flight assist turns at when steering all the way.
+    pub max_turn_rate: f64,
+}
+
+/// The fighter flown by the player; its mass and inertia are those of the body the physics feature gives ships.
+pub const FIGHTER_FLIGHT_MODEL: FlightModel = FlightModel {
+    mass: 1.0,
+    inertia: 0.1,
+    max_thrust: 30.0,
+    max_rcs_force: 10.0,
+    max_rcs_torque: 0.3,
+    max_turn_rate: 1.2,
+};
+
+/// Set the main engine of a ship to a fraction of its thrust, from 0 to 1.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Throttle(pub ShipId, pub f64);
+
+impl Keyed for Throttle {
+    type Key = ShipId;
+
+    fn key(&self) -> &ShipId {
+        &self.0
+    }
+}
+
+/// Steer a ship around its own x (pitch), y (yaw) and z (roll) axes, each from -1 to 1.
+///
+/// Without flight assist, this is the fraction of the RCS torque to apply; with it, the fraction
+/// of the maximum turn rate to hold.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Steer(pub ShipId, pub Vector3<f64>);
+
+impl Keyed for Steer {
+    type Key = ShipId;
+
+    fn key(&self) -> &ShipId {
+        &self.0
+    }
+}
+
+/// Turn flight assist on or off. With it on, the RCS cancels any drift sideways from the nose,
+/// and holds the turn rate that is steered for.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct FlightAssist(pub ShipId, pub bool);
+
+impl Keyed for FlightAssist {
+    type Key = ShipId;
+
+    fn key(&self) -> &ShipId {
+        &self.0
+    }
+}
+
+struct FlightState {
+    pose: Isometry3<f64>,
+    /// In the world frame.
+    velocity: Vector3<f64>,
+    /// In the ship's own frame.
+    angular_velocity: Vector3<f64>,
+    throttle: f64,
+    steer: Vector3<f64>,
+    assist: bool,
+}
+
+fn clamp_norm(v: Vector3<f64>, max: f64) -> Vector3<f64> {
+    if v.norm() > max { v.normalize() * max } else { v }
+}
+
+impl FlightModel {
+    /// The force and torque in the ship's own frame that the controls call for over the next dt seconds.
+    fn forces(&self, state: &FlightState, dt: f64) -> (Vector3<f64>, Vector3<f64>) {
+        let mut force = Vector3::new(0.0, 0.0, -self.max_thrust * state.throttle);
+
+        let torque = if state.assist {
+            let velocity = state.pose.rotation.inverse() * state.velocity;
+            let drift = Vector3::new(velocity.x, velocity.y, 0.0);
+            force += clamp_norm(-drift * self.mass / dt, self.max_rcs_force);
+
+            let turn = state.steer * self.max_turn_rate - state.angular_velocity;
+            clamp_norm(turn * self.inertia / dt, self.max_rcs_torque)
+        } else {
+            clamp_norm(state.steer * self.max_rcs_torque, self.max_rcs_torque)
+        };
+
+        (force, torque)
+    }
+
+    /// Move the ship along for dt seconds under the given force and torque in its own frame.
+    #[cfg(not(feature = "physics"))]
+    fn integrate(&self, state: &mut FlightState, force: Vector3<f64>, torque: Vector3<f64>, dt: f64) {
+        state.velocity += state.pose.rotation * force / self.mass * dt;
+        state.pose.translation.vector += state.velocity * dt;
+
+        // The inertia is the same around every axis, so turning does not make the ship tumble.
+        state.angular_velocity += torque / self.inertia * dt;
+        state.pose.rotation *= UnitQuaternion::from_scaled_axis(state.angular_velocity * dt);
+        state.pose.rotation.renormalize();
+    }
+}
+
+/// Fly a ship by the given flight model, controlled by Throttle, Steer and FlightAssist messages.
+///
+/// The ship starts out where its ShipCreated says, at rest, with the engine off and flight assist on.
+pub fn create_flight_model(system: &mut System, ship_id: ShipId, model: FlightModel) {
+    let builder = ActorBuilder::new(FlightState {
+        pose: Isometry3::identity(),
+        velocity: Vector3::zeros(),
+        angular_velocity: Vector3::zeros(),
+        throttle: 0.0,
+        steer: Vector3::zeros(),
+        assist: true,
+    })
+        .with_keyed_handler(ship_id, |state, ShipCreated(_, at), _| {
+            state.pose = *at;
+            Keep
+        })
+        .with_keyed_handler(ship_id, |state, Throttle(_, throttle), _| {
+            state.throttle = throttle.clamp(0.0, 1.0);
+            Keep
+        })
+        .with_keyed_handler(ship_id, |state, Steer(_, steer), _| {
+            state.steer = steer.map(|s| s.clamp(-1.0, 1.0));
+            Keep
+        })
+        .with_keyed_handler(ship_id, |state, FlightAssist(_, assist), _| {
+            state.assist = *assist;
+            Keep
+        });
+
+    #[cfg(not(feature = "physics"))]
+    let builder = builder.with_handler(move |state, tick: &Tick, outbox| {
+        let dt = tick.dt.as_secs_f64();
+        let (force, torque) = model.forces(state, dt);
+        model.integrate(state, force, torque, dt);
+
+        outbox.send(ShipMoved(ship_id, state.pose));
+
+        Keep
+    });
+
+    #[cfg(feature = "physics")]
+    let builder = {
+        use crate::physics::{ShipThrust, ShipTorque, ShipVelocity};
+
+        builder
+            .with_handler(move |state, tick: &Tick, outbox| {
+                let (force, torque) = model.forces(state, tick.dt.as_secs_f64());
+
+                outbox.send(ShipThrust(ship_id, force));
+                outbox.send(ShipTorque(ship_id, torque));
+
+                Keep
+            })
+            .with_keyed_handler(ship_id, |state, ShipMoved(_, at), _| {
+                state.pose = *at;
+                Keep
+            })
+            .with_keyed_handler(ship_id, |state, ShipVelocity(_, linear, angular), _| {
+                state.velocity = *linear;
+                state.angular_velocity = state.pose.rotation.inverse() * angular;
+                Keep
+            })
+    };
+
+    system.create_actor(builder.build());
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::mpsc::channel;
+    use std::time::Duration;
+
+    use nalgebra::{Isometry3, Vector3};
+
+    use voyagers_core::actors::{ActorBuilder, System};
+    use voyagers_core::actors::Fate::Keep;
+    use voyagers_core::clock::FixedTimestep;
+
+    use crate::flight::{create_flight_model, FIGHTER_FLIGHT_MODEL, FlightAssist, Steer, Throttle};
+    use crate::game::{ShipCreated, ShipId, ShipMoved};
+
+    #[test]
+    fn ships_keep_their_momentum_unless_assisted() {
+        let mut system = System::new();
+        let mut timestep = FixedTimestep::new(Duration::from_millis(10));
+
+        #[cfg(feature = "physics")]
+        crate::physics::init_physics(&mut system);
+
+        let ship = ShipId(1);
+        create_flight_model(&mut system, ship, FIGHTER_FLIGHT_MODEL);
+
+        let (tx, rx) = channel();
+        system.create_actor(ActorBuilder::new(())
+            .with_keyed_handler(ship, move |_, ShipMoved(_, at), _| {
+                tx.send(*at).unwrap();
+                Keep
+            }).build());
+
+        // Well away from the station at the origin.
+        system.send(ShipCreated(ship, Isometry3::translation(50.0, 0.0, 0.0)));
+        system.send(FlightAssist(ship, false));
+
+        let mut fly = |system: &mut System, seconds: u32| {
+            for _ in 0..seconds * 100 {
+                system.send(timestep.step());
+                while system.handle_one() {};
+            }
+            let poses = rx.try_iter().collect::<Vec<_>>();
+            let (before, last) = (poses[poses.len() - 2], poses[poses.len() - 1]);
+            (last, (last.translation.vector - before.translation.vector) * 100.0)
+        };
+
+        // One second at full throttle, then coasting along.
+        system.send(Throttle(ship, 1.0));
+        let (_, velocity) = fly(&mut system, 1);
+        assert!((velocity.z + FIGHTER_FLIGHT_MODEL.max_thrust).abs() < 1.0, "{}", velocity);
+
+        system.send(Throttle(ship, 0.0));
+        let (_, coasting) = fly(&mut system, 1);
+        assert!((coasting - velocity).norm() < 1.0, "{} {}", velocity, coasting);
+
+        // Turned away from where it is going, the ship keeps drifting the same way.
+        system.send(Steer(ship, Vector3::new(0.0, 1.0, 0.0)));
+        let (_, _) = fly(&mut system, 1);
+        system.send(Steer(ship, Vector3::zeros()));
+        let (turned, drifting) = fly(&mut system, 1);
+        let sideways = |pose: Isometry3<f64>, velocity: Vector3<f64>| (pose.rotation.inverse() * velocity).xy().norm();
+        assert!(sideways(turned, drifting) > 10.0, "{}", drifting);
+
+        // Flight assist stops the drift, and the turning.
+        system.send(FlightAssist(ship, true));
+        let (assisted, velocity) = fly(&mut system, 10);
+        assert!(sideways(assisted, velocity) < 0.1, "{}", velocity);
+        let (still, _) = fly(&mut system, 1);
+        assert!(still.rotation.angle_to(&assisted.rotation) < 0.01);
+    }
+}
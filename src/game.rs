@@ -0,0 +1,889 @@
+use std::cell::Cell;
+use std::rc::Rc;
+use std::time::Duration;
+
+use nalgebra::{Isometry3, Point3, Vector3};
+use rand::{Rng, SeedableRng};
+use rand::rngs::StdRng;
+
+use voyagers_core::actors::{ActorBuilder, ActorData, ActorFailed, Keyed, System, SystemInterface};
+use voyagers_core::actors::Fate::{Become, Keep};
+use voyagers_core::clock::Tick;
+use voyagers_core::delay;
+use voyagers_core::delay::{delay_from_now, Schedule};
+use voyagers_core::expect::TimedOut;
+use voyagers_core::replay::Recordable;
+
+/// The echo of a scan pulse of the given ship off an asteroid, with the strength it came back at.
+#[derive(Clone)]
+pub struct ScanPing(pub ShipId, pub Point3<f64>, pub f64);
+
+impl Keyed for ScanPing {
+    type Key = ShipId;
+
+    fn key(&self) -> &ShipId {
+        &self.0
+    }
+}
+
+impl Interpretable for ScanPing {
+    fn interpret(&self) -> String {
+        format!("A scanner pulse of ship {:?} bounced off something at {} with strength {}", self.0, self.1, self.2)
+    }
+}
+
+/// A ship scanning for asteroids from where it is.
+#[derive(Clone)]
+pub struct ScanPulse(pub ShipId, pub Point3<f64>, pub Scanner);
+
+impl Interpretable for ScanPulse {
+    fn interpret(&self) -> String {
+        format!("Ship {:?} sent a scanner pulse out from {}", self.0, self.1)
+    }
+}
+
+/// What a class of ship scans with.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Scanner {
+    /// Asteroids further away than this do not answer at all.
+    pub range: f64,
+    /// The weakest ping the scanner picks up.
+    pub sensitivity: f64,
+}
+
+impl Scanner {
+    /// How long to wait for pings to come back from the edge of the range.
+    pub fn timeout(&self) -> Duration {
+        Duration::from_secs_f64(self.range / SCAN_PING_SPEED)
+    }
+}
+
+pub const MINER_SCANNER: Scanner = Scanner { range: 150.0, sensitivity: 1e-4 };
+
+/// The player fires it from the window, so it only exists with the render feature.
+#[cfg(feature = "render")]
+pub const FIGHTER_SCANNER: Scanner = Scanner { range: 400.0, sensitivity: 1e-5 };
+
+const SCAN_PING_SPEED: f64 = 10.0;
+
+/// How strong the ping of an asteroid is: bigger ones reflect more, and it falls off with the square of the distance.
+fn ping_strength(radius: f64, distance: f64) -> f64 {
+    (radius / distance.max(radius)).powi(2)
+}
+
+trait Interpretable {
+    fn interpret(&self) -> String;
+}
+
+#[derive(Clone, Copy, Eq, PartialEq, Hash, Debug)]
+pub struct ShipId(pub u64);
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct ShipDestination(pub ShipId, pub Point3<f64>);
+
+impl Keyed for ShipDestination {
+    type Key = ShipId;
+
+    fn key(&self) -> &ShipId {
+        &self.0
+    }
+}
+
+impl Interpretable for ShipDestination {
+    fn interpret(&self) -> String {
+        format!("Ship {:?} will travel to destination {}", self.0, self.1)
+    }
+}
+
+#[derive(Clone, Copy, Eq, PartialEq, Hash, Debug)]
+pub struct ShipArrived(pub ShipId);
+
+impl Keyed for ShipArrived {
+    type Key = ShipId;
+
+    fn key(&self) -> &ShipId {
+        &self.0
+    }
+}
+
+impl Interpretable for ShipArrived {
+    fn interpret(&self) -> String {
+        format!("Ship {:?} has arrived at its planned destination.", self.0)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct ShipMoved(pub ShipId, pub Isometry3<f64>);
+
+impl Keyed for ShipMoved {
+    type Key = ShipId;
+
+    fn key(&self) -> &ShipId {
+        &self.0
+    }
+}
+
+impl Interpretable for ShipMoved {
+    fn interpret(&self) -> String {
+        format!("Ship {:?} has moved to position {}", self.0, self.1)
+    }
+}
+
+/// A ship has come into being at the given pose.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct ShipCreated(pub ShipId, pub Isometry3<f64>);
+
+impl Keyed for ShipCreated {
+    type Key = ShipId;
+
+    fn key(&self) -> &ShipId {
+        &self.0
+    }
+}
+
+impl Interpretable for ShipCreated {
+    fn interpret(&self) -> String {
+        format!("Ship {:?} was launched at {}", self.0, self.1)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct AsteroidCollected(pub Point3<f64>);
+
+impl Interpretable for AsteroidCollected {
+    fn interpret(&self) -> String {
+        format!("Asteroid at {} has been collected.", self.0)
+    }
+}
+
+#[derive(Clone, Copy, Eq, PartialEq, Hash, Debug)]
+pub struct ScoreChangedTo(pub u64);
+
+impl Interpretable for ScoreChangedTo {
+    fn interpret(&self) -> String {
+        format!("The score has been changed to {} points.", self.0)
+    }
+}
+
+struct ShipBehaviorControllerState {
+    position: Point3<f64>,
+    ship_id: ShipId,
+    /// Picks where to move to when a scan comes up empty.
+    rng: StdRng,
+}
+
+/// An asteroid with the given radius came into being at the given point.
+#[derive(Copy, Clone)]
+pub struct AsteroidCreated(pub Point3<f64>, pub f64);
+
+impl Recordable for AsteroidCreated {
+    fn encode(&self) -> String {
+        format!("{} {} {} {}", self.0.x, self.0.y, self.0.z, self.1)
+    }
+
+    fn decode(text: &str) -> Option<Self> {
+        let coords: Vec<f64> = text.split(' ').map(|c| c.parse().ok()).collect::<Option<_>>()?;
+        match coords[..] {
+            [x, y, z, radius] => Some(AsteroidCreated(Point3::new(x, y, z), radius)),
+            _ => None,
+        }
+    }
+}
+
+fn random_asteroid(rng: &mut StdRng) -> AsteroidCreated {
+    AsteroidCreated(Point3::new(
+        rng.gen_range(-100.0f64..100.0),
+        rng.gen_range(-100.0..100.0),
+        rng.gen_range(-100.0..100.0),
+    ), rng.gen_range(0.5..2.0))
+}
+
+#[derive(Clone)]
+pub struct StartShip(pub ShipId);
+
+impl Keyed for StartShip {
+    type Key = ShipId;
+
+    fn key(&self) -> &ShipId {
+        &self.0
+    }
+}
+
+impl Interpretable for ActorFailed {
+    fn interpret(&self) -> String {
+        format!("Actor {:?} failed while handling a {}: {}", self.actor, self.message_type, self.payload)
+    }
+}
+
+#[derive(Clone)]
+pub struct TransponderBroadcast(pub String, pub Point3<f64>);
+
+impl Interpretable for TransponderBroadcast {
+    fn interpret(&self) -> String {
+        format!("Transponder with ID {} broadcast from {}.", self.0, self.1)
+    }
+}
+
+#[cfg(feature = "physics")]
+impl Interpretable for crate::physics::CollisionStarted {
+    fn interpret(&self) -> String {
+        format!("{:?} ran into {:?} with an impulse of {}.", self.0, self.1, self.2)
+    }
+}
+
+#[cfg(feature = "physics")]
+impl Interpretable for crate::physics::CollisionEnded {
+    fn interpret(&self) -> String {
+        format!("{:?} and {:?} came apart.", self.0, self.1)
+    }
+}
+
+/// Reads the score out loud as it changes, and any actor that fails.
+#[cfg(feature = "tts")]
+fn create_speaking_narrator(system: &mut System) {
+    let tts = match tts::Tts::default() {
+        Ok(tts) => tts,
+        Err(e) => {
+            println!("Not narrating out loud, text to speech is unavailable: {}", e);
+            return;
+        }
+    };
+
+    // A failed utterance only means one thing goes unsaid.
+    system.create_actor(ActorBuilder::new(tts)
+        .with_handler(|tts, evt: &ScoreChangedTo, _| {
+            tts.speak(evt.interpret(), true).ok();
+            Keep
+        })
+        .with_handler(|tts, evt: &ActorFailed, _| {
+            tts.speak(evt.interpret(), false).ok();
+            Keep
+        }).build());
+}
+
+/// Time for the transponder of the given ship to broadcast again.
+#[derive(Clone)]
+struct TransponderDue(ShipId);
+
+impl Keyed for TransponderDue {
+    type Key = ShipId;
+
+    fn key(&self) -> &ShipId {
+        &self.0
+    }
+}
+
+/// Broadcast the ID of the ship and where it last moved to, every 100 ticks.
+fn create_transponder(system: &mut System, ship_id: ShipId, id: String, starting_point: Point3<f64>) {
+    system.create_actor(ActorBuilder::new(starting_point)
+        .with_keyed_handler(ship_id, |position, ShipMoved(_, to), _| {
+            *position = to.translation.vector.into();
+            Keep
+        })
+        .with_keyed_handler(ship_id, move |position, _: &TransponderDue, outbox| {
+            outbox.send(TransponderBroadcast(id.clone(), *position));
+            Keep
+        }).build());
+
+    delay::repeat(&mut system.input_interface, TransponderDue(ship_id), Schedule::every_ticks(100));
+}
+
+fn create_debug_narrator(system: &mut System) {
+    let builder = ActorBuilder::new(())
+        .with_handler(move |_, evt: &ScanPulse, _| {
+            println!("{}", evt.interpret());
+            Keep
+        })
+        // .with_handler(move |_,evt:&ScanPing,_|          {println!("{}", evt.interpret()); Keep})
+        .with_handler(move |_, evt: &ShipDestination, _| {
+            println!("{}", evt.interpret());
+            Keep
+        })
+        .with_handler(move |_, evt: &ShipCreated, _| {
+            println!("{}", evt.interpret());
+            Keep
+        })
+        .with_handler(move |_, evt: &ShipArrived, _| {
+            println!("{}", evt.interpret());
+            Keep
+        })
+        .with_handler(move |_, evt: &AsteroidCollected, _| {
+            println!("{}", evt.interpret());
+            Keep
+        })
+        .with_handler(move |_, evt: &ScoreChangedTo, _| {
+            println!("{}", evt.interpret());
+            Keep
+        })
+        .with_handler(move |_, evt: &ActorFailed, _| {
+            println!("{}", evt.interpret());
+            Keep
+        })
+        .with_handler(move |_, evt: &TransponderBroadcast, _| {
+            println!("{}", evt.interpret());
+            Keep
+        });
+
+    #[cfg(feature = "physics")]
+    let builder = builder
+        .with_handler(move |_, evt: &crate::physics::CollisionStarted, _| {
+            println!("{}", evt.interpret());
+            Keep
+        })
+        .with_handler(move |_, evt: &crate::physics::CollisionEnded, _| {
+            println!("{}", evt.interpret());
+            Keep
+        });
+
+    system.create_actor(builder.build());
+}
+
+/// Whether the straight line from one point to another passes through the sphere.
+#[cfg(not(feature = "physics"))]
+fn segment_hits_sphere(from: Point3<f64>, to: Point3<f64>, center: Point3<f64>, radius: f64) -> bool {
+    let d = to - from;
+    let t = if d.norm_squared() == 0.0 { 0.0 } else { ((center - from).dot(&d) / d.norm_squared()).clamp(0.0, 1.0) };
+    (from + d * t - center).norm() < radius
+}
+
+/// Half the thickness, and the inner and outer radius, of the ringstation lying flat around the origin.
+///
+/// These are the bounds of the vertices in models/ringstation.obj, and have to be kept in line with it
+/// by hand. The physics feature casts rays against the model itself instead.
+#[cfg(not(feature = "physics"))]
+const STATION_HALF_HEIGHT: f64 = 2.5;
+#[cfg(not(feature = "physics"))]
+const STATION_INNER_RADIUS: f64 = 4.1;
+#[cfg(not(feature = "physics"))]
+const STATION_OUTER_RADIUS: f64 = 15.0;
+
+/// Whether the straight line from one point to another passes through the ringstation.
+///
+/// The ring is round, so this holds however far it has spun.
+#[cfg(not(feature = "physics"))]
+fn segment_hits_station(from: Point3<f64>, to: Point3<f64>) -> bool {
+    let d = to - from;
+
+    // The part of the line between the top and the bottom of the ring.
+    let (t0, t1) = if d.y == 0.0 {
+        if from.y.abs() > STATION_HALF_HEIGHT {
+            return false;
+        }
+        (0.0, 1.0)
+    } else {
+        let (a, b) = ((-STATION_HALF_HEIGHT - from.y) / d.y, (STATION_HALF_HEIGHT - from.y) / d.y);
+        (a.min(b).max(0.0), a.max(b).min(1.0))
+    };
+    if t0 > t1 {
+        return false;
+    }
+
+    // The squared distance from the axis along that part comes closest at one point and is furthest at an end,
+    // and takes every value in between.
+    let from_axis = |t: f64| {
+        let p = from + d * t;
+        p.x * p.x + p.z * p.z
+    };
+    let horizontal = d.x * d.x + d.z * d.z;
+    let closest = if horizontal == 0.0 { t0 } else { (-(from.x * d.x + from.z * d.z) / horizontal).clamp(t0, t1) };
+
+    from_axis(closest) <= STATION_OUTER_RADIUS.powi(2) && from_axis(t0).max(from_axis(t1)) >= STATION_INNER_RADIUS.powi(2)
+}
+
+/// Send the pings of the asteroids in sight of a pulse that are in range and big enough to be picked up.
+fn echo<'a>(outbox: &mut SystemInterface, ScanPulse(ship_id, pos, scanner): &ScanPulse, in_sight: impl Iterator<Item=&'a (Point3<f64>, f64)>) {
+    for (pt, radius) in in_sight {
+        let distance = (pos - pt).norm();
+        let strength = ping_strength(*radius, distance);
+        if distance <= scanner.range && strength >= scanner.sensitivity {
+            delay::delay_from_now(outbox, ScanPing(*ship_id, *pt, strength), Duration::from_secs_f64(distance / SCAN_PING_SPEED));
+        }
+    }
+}
+
+/// Answers scan pulses with a ping from every asteroid that is in range, big enough to be picked up,
+/// and not hidden behind the ringstation or another asteroid.
+///
+/// With the physics feature, the physics world casts the rays and says which asteroids are in sight.
+fn create_scan_echoes(system: &mut System) {
+    let builder = ActorBuilder::new(Vec::<(Point3<f64>, f64)>::new())
+        .with_handler(|asteroids, AsteroidCreated(at, radius), _| {
+            asteroids.push((*at, *radius));
+            Keep
+        })
+        .with_handler(|asteroids, AsteroidCollected(at), _| {
+            asteroids.retain(|(pt, _)| pt != at);
+            Keep
+        });
+
+    #[cfg(not(feature = "physics"))]
+    let builder = builder.with_handler(|asteroids, pulse: &ScanPulse, outbox| {
+        let ScanPulse(_, pos, _) = pulse;
+        let in_sight = asteroids.iter().filter(|(pt, _)| {
+            !segment_hits_station(*pos, *pt) && !asteroids.iter()
+                .any(|(other, other_radius)| other != pt && segment_hits_sphere(*pos, *pt, *other, *other_radius))
+        });
+        echo(outbox, pulse, in_sight);
+        Keep
+    });
+
+    #[cfg(feature = "physics")]
+    let builder = builder.with_handler(|asteroids, crate::physics::AsteroidsInSight(pulse, in_sight), outbox| {
+        echo(outbox, pulse, asteroids.iter().filter(|(pt, _)| in_sight.contains(pt)));
+        Keep
+    });
+
+    system.create_actor(builder.build());
+}
+
+fn create_mining_ship_high_level_behavior_controller(system: &mut System, ship_id: ShipId, starting_point: Point3<f64>, rng: StdRng) {
+    system.create_actor(mining_ship_ready(ActorBuilder::new(ShipBehaviorControllerState {
+        position: starting_point,
+        ship_id,
+        rng,
+    }), ship_id));
+}
+
+/// Handlers shared by all behaviors of a mining ship.
+fn mining_ship_behavior(builder: ActorBuilder<ShipBehaviorControllerState>, ship_id: ShipId) -> ActorBuilder<ShipBehaviorControllerState> {
+    builder.with_keyed_handler(ship_id, move |state, ShipMoved(_, to), _| {
+        state.position = to.translation.vector.into();
+
+        Keep
+    })
+}
+
+/// Drop the pings that come back once the ship stopped listening, such as those of asteroids further away than the one it went for.
+fn ignoring_late_pings(builder: ActorBuilder<ShipBehaviorControllerState>, ship_id: ShipId) -> ActorBuilder<ShipBehaviorControllerState> {
+    builder.with_keyed_handler(ship_id, |_, _: &ScanPing, _| Keep)
+}
+
+fn mining_ship_ready(builder: ActorBuilder<ShipBehaviorControllerState>, ship_id: ShipId) -> ActorData {
+    ignoring_late_pings(mining_ship_behavior(builder, ship_id), ship_id)
+        .with_keyed_handler(ship_id, move |state, StartShip(_), outbox| {
+            outbox.send(ScanPulse(ship_id, state.position, MINER_SCANNER));
+            outbox.expect(move |ping: &ScanPing| ping.0 == ship_id, MINER_SCANNER.timeout());
+
+            Become(mining_ship_waiting_for_ping(ActorBuilder::behavior(), ship_id))
+        }).build()
+}
+
+fn mining_ship_waiting_for_ping(builder: ActorBuilder<ShipBehaviorControllerState>, ship_id: ShipId) -> ActorData {
+    mining_ship_behavior(builder, ship_id)
+        .with_keyed_handler(ship_id, move |state, ScanPing(_, at, _), outbox| {
+            let delta = at - state.position;
+
+            outbox.send(ShipDestination(state.ship_id, at - delta.normalize() * 1.0));
+
+            Become(mining_ship_approaching_asteroid(ActorBuilder::behavior(), ship_id, *at))
+        })
+        .with_handler(move |state, _: &TimedOut<ScanPing>, outbox| {
+            // Nothing in sight from here; move somewhere nearby and scan again from there.
+            const REPOSITION_DISTANCE: f64 = 40.0;
+            let offset = Vector3::from_fn(|_, _| state.rng.gen_range(-REPOSITION_DISTANCE..REPOSITION_DISTANCE));
+            let destination = (state.position + offset).map(|c| c.clamp(-100.0, 100.0));
+
+            outbox.send(ShipDestination(state.ship_id, destination));
+
+            Become(mining_ship_repositioning(ActorBuilder::behavior(), ship_id))
+        }).build()
+}
+
+fn mining_ship_repositioning(builder: ActorBuilder<ShipBehaviorControllerState>, ship_id: ShipId) -> ActorData {
+    ignoring_late_pings(mining_ship_behavior(builder, ship_id), ship_id)
+        .with_keyed_handler(ship_id, move |state, ShipArrived(_), outbox| {
+            outbox.send(StartShip(state.ship_id));
+
+            Become(mining_ship_ready(ActorBuilder::behavior(), ship_id))
+        }).build()
+}
+
+fn mining_ship_approaching_asteroid(builder: ActorBuilder<ShipBehaviorControllerState>, ship_id: ShipId, asteroid: Point3<f64>) -> ActorData {
+    let collect = move |state: &mut ShipBehaviorControllerState, outbox: &mut SystemInterface| {
+        outbox.send(AsteroidCollected(asteroid));
+
+        delay::delay_from_now(outbox, StartShip(state.ship_id), Duration::from_secs(5));
+
+        Become(mining_ship_ready(ActorBuilder::behavior(), ship_id))
+    };
+
+    let builder = ignoring_late_pings(mining_ship_behavior(builder, ship_id), ship_id)
+        .with_keyed_handler(ship_id, move |state, ShipArrived(_), outbox| collect(state, outbox));
+
+    // Bumping into the asteroid on the way in is as good as arriving next to it.
+    #[cfg(feature = "physics")]
+    let builder = builder.with_handler(move |state, crate::physics::CollisionStarted(a, b, _), outbox| {
+        use crate::physics::Entity::{Asteroid, Ship};
+
+        match (a, b) {
+            (Ship(id), Asteroid(at)) | (Asteroid(at), Ship(id)) if *id == ship_id && *at == asteroid => collect(state, outbox),
+            _ => Keep,
+        }
+    });
+
+    builder.build()
+}
+
+#[derive(Clone, Copy)]
+struct ShipMovementController {
+    position: Point3<f64>,
+    destination: Option<Point3<f64>>,
+    ship_id: ShipId,
+}
+
+/// A restarted controller carries on from where the ship last moved to, not from its starting point.
+#[cfg(not(feature = "physics"))]
+fn ship_movement_controller(ship_id: ShipId, starting_point: Point3<f64>) -> ActorBuilder<ShipMovementController> {
+    let initial = ShipMovementController {
+        ship_id,
+        position: starting_point,
+        destination: None,
+    };
+    let last_moved = Rc::new(Cell::new(initial));
+    let restart_from = last_moved.clone();
+
+    ActorBuilder::new(initial).with_handler(move |state, tick: &Tick, outbox| {
+        if let Some(destination) = &state.destination {
+            // Units per second.
+            const SPEED: f64 = 6.0;
+            let step = SPEED * tick.dt.as_secs_f64();
+            if (destination - state.position).norm() < step {
+                state.position = *destination;
+                state.destination = None;
+
+                outbox.send(ShipArrived(state.ship_id));
+            } else {
+                state.position += (destination - state.position).normalize() * step;
+            }
+        }
+        outbox.send(ShipMoved(state.ship_id, Isometry3::translation(state.position.x, state.position.y, state.position.z)));
+        last_moved.set(*state);
+
+        Keep
+    }).with_keyed_handler(ship_id, move |state, ShipDestination(_, pos), _| {
+        state.destination = Some(*pos);
+        Keep
+    }).restart_on_panic_with(move || restart_from.get())
+}
+
+/// With physics, ships are flown to their destination by thrusting, and so can be bumped off course.
+/// A restarted controller carries on from the last ShipMoved the physics world sent it.
+#[cfg(feature = "physics")]
+fn ship_movement_controller(ship_id: ShipId, starting_point: Point3<f64>) -> ActorBuilder<(ShipMovementController, Isometry3<f64>, Vector3<f64>)> {
+    use crate::physics::{SHIP_MASS, ShipThrust, ShipVelocity};
+
+    // Units per second, units per second squared, and how close counts as arrived.
+    const SPEED: f64 = 6.0;
+    const MAX_ACCELERATION: f64 = 10.0;
+    const ARRIVAL_DISTANCE: f64 = 1.0;
+
+    let initial = (ShipMovementController {
+        ship_id,
+        position: starting_point,
+        destination: None,
+    }, Isometry3::translation(starting_point.x, starting_point.y, starting_point.z), Vector3::zeros());
+    let last_moved = Rc::new(Cell::new(initial));
+    let restart_from = last_moved.clone();
+
+    ActorBuilder::new(initial)
+        .with_handler(move |(state, pose, velocity), _: &Tick, outbox| {
+            let mut desired_velocity = Vector3::zeros();
+
+            if let Some(destination) = &state.destination {
+                let offset = destination - state.position;
+                if offset.norm() < ARRIVAL_DISTANCE && velocity.norm() < ARRIVAL_DISTANCE {
+                    state.destination = None;
+
+                    outbox.send(ShipArrived(state.ship_id));
+                } else {
+                    // Slow down over the last SPEED units.
+                    desired_velocity = offset.normalize() * SPEED.min(offset.norm());
+                }
+            }
+
+            let mut acceleration = desired_velocity - *velocity;
+            if acceleration.norm() > MAX_ACCELERATION {
+                acceleration = acceleration.normalize() * MAX_ACCELERATION;
+            }
+            outbox.send(ShipThrust(state.ship_id, pose.rotation.inverse() * acceleration * SHIP_MASS));
+
+            Keep
+        })
+        .with_keyed_handler(ship_id, move |controller, ShipMoved(_, to), _| {
+            let (state, pose, _) = controller;
+            state.position = to.translation.vector.into();
+            *pose = *to;
+            last_moved.set(*controller);
+            Keep
+        })
+        .with_keyed_handler(ship_id, move |(_, _, velocity), ShipVelocity(_, v, _), _| {
+            *velocity = *v;
+            Keep
+        })
+        .with_keyed_handler(ship_id, move |(state, _, _), ShipDestination(_, pos), _| {
+            state.destination = Some(*pos);
+            Keep
+        }).restart_on_panic_with(move || restart_from.get())
+}
+
+/// Game time per Tick.
+pub const TIMESTEP: Duration = Duration::from_nanos(1_000_000_000 / 60);
+
+/// The mining ships are numbered from zero up to this.
+pub const MINING_SHIPS: u64 = 5;
+
+#[cfg(feature = "render")]
+pub const PIRATE: ShipId = ShipId(69);
+
+/// The ship flown by the player.
+#[cfg(feature = "render")]
+pub const FIGHTER: ShipId = ShipId(555);
+
+/// How fast the ringstation at the center spins around its axis, in radians per second.
+#[cfg(any(feature = "render", feature = "physics"))]
+pub const STATION_SPIN: f64 = 0.6;
+
+/// Start the actors that make up the game itself, everything but rendering and input.
+pub fn init_game(system: &mut System, rng: &mut StdRng) {
+    for _ in 0..100 {
+        system.send(random_asteroid(rng));
+    }
+
+    system.create_actor(ActorBuilder::new(StdRng::seed_from_u64(rng.gen())).with_handler(|rng, _: &AsteroidCollected, outbox| {
+        delay_from_now(outbox, random_asteroid(rng), Duration::from_secs(5));
+
+        Keep
+    }).build());
+
+    create_scan_echoes(system);
+
+    for ship_id in 0..MINING_SHIPS {
+        let ship_id = ShipId(ship_id);
+
+        let starting_point = Point3::new(
+            rng.gen_range(-100.0f64..100.0),
+            rng.gen_range(-100.0..100.0),
+            rng.gen_range(-100.0..100.0),
+        );
+
+        system.send(ShipCreated(ship_id, Isometry3::translation(starting_point.x, starting_point.y, starting_point.z)));
+
+        system.create_actor(ship_movement_controller(ship_id, starting_point).build());
+
+        create_mining_ship_high_level_behavior_controller(system, ship_id, starting_point, StdRng::seed_from_u64(rng.gen()));
+
+        create_transponder(system, ship_id, format!("Mineral collection barge {}.", ship_id.0), starting_point);
+
+        delay::delay_from_now(&mut system.input_interface, StartShip(ship_id), Duration::from_secs(5));
+    }
+
+    system.create_actor(ActorBuilder::new(0)
+        .with_handler(move |score, _: &AsteroidCollected, outbox| {
+            *score += 1;
+            outbox.send(ScoreChangedTo(*score));
+            Keep
+        }).build());
+
+    #[cfg(feature = "physics")]
+    crate::physics::init_physics(system);
+
+    delay::init_delay_handler(system, rng.gen());
+
+    create_debug_narrator(system);
+
+    #[cfg(feature = "tts")]
+    create_speaking_narrator(system);
+}
+
+/// Start the fighter and the pirate, which are only there to be flown and looked at, so only the window has them.
+#[cfg(feature = "render")]
+pub fn init_fighter_and_pirate(system: &mut System) {
+    use voyagers_core::parallel::SendActorBuilder;
+
+    use crate::flight::{create_flight_model, FIGHTER_FLIGHT_MODEL, Steer, Throttle};
+
+    // Out in front of the ringstation.
+    system.send(ShipCreated(FIGHTER, Isometry3::translation(0.0, 5.0, 30.0)));
+    create_flight_model(system, FIGHTER, FIGHTER_FLIGHT_MODEL);
+
+    // The pirate flies a fighter too, straight on until it gets too far out and turns back to pass over the station.
+    let pirate_start = Isometry3::translation(100.0, 100.0, 100.0);
+    system.send(ShipCreated(PIRATE, pirate_start));
+    create_flight_model(system, PIRATE, FIGHTER_FLIGHT_MODEL);
+
+    system.create_send_actor(SendActorBuilder::new((pirate_start, pirate_start.translation.vector))
+        .with_handler(|(pose, _), ShipMoved(id, to), _| {
+            if *id == PIRATE {
+                *pose = *to;
+            }
+            Keep
+        })
+        .with_handler(|(pose, last_position), tick: &Tick, outbox| {
+            // Units per second, how far out from the center it turns back, and how high over the station it aims.
+            const SPEED: f64 = 60.0;
+            const RANGE: f64 = 200.0;
+            const CLEARANCE: f64 = 50.0;
+
+            let position = pose.translation.vector;
+            let forward = pose.rotation * -Vector3::z();
+            let speed = (position - *last_position).dot(&forward) / tick.dt.as_secs_f64();
+            *last_position = position;
+
+            // Pitching up turns the nose towards +y, and yawing left towards -x.
+            // With the aim behind, turn as hard as possible, whichever way is closest.
+            let steer = if position.norm() > RANGE {
+                let aim = pose.rotation.inverse() * (Vector3::y() * CLEARANCE - position).normalize();
+                let turn = Vector3::new(aim.y, -aim.x, 0.0);
+                match aim.z > 0.0 {
+                    true => turn.try_normalize(1e-6).unwrap_or_else(Vector3::y),
+                    false => turn * 2.0,
+                }
+            } else {
+                Vector3::zeros()
+            };
+
+            outbox.send(Steer(PIRATE, steer));
+            outbox.send(Throttle(PIRATE, (SPEED - speed) / 10.0));
+
+            Keep
+        }).build());
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use nalgebra::{Isometry3, Point3};
+
+    use voyagers_core::testing::{collect, test_system};
+
+    use crate::game::{AsteroidCreated, create_scan_echoes, create_transponder, MINER_SCANNER, ScanPing, ScanPulse, ship_movement_controller, ShipCreated, ShipDestination, ShipId, ShipMoved, TransponderBroadcast};
+
+    /// Scan once from the given point, among the given asteroids, and collect the pings that come back.
+    fn scan(from: Point3<f64>, asteroids: &[AsteroidCreated]) -> Vec<(ShipId, Point3<f64>, f64)> {
+        let (mut system, mut ticks) = test_system(Duration::from_millis(100));
+
+        create_scan_echoes(&mut system);
+
+        #[cfg(feature = "physics")]
+        crate::physics::init_physics(&mut system);
+
+        for asteroid in asteroids {
+            system.send(*asteroid);
+        }
+
+        let rx = collect(&mut system, |ScanPing(ship_id, at, strength), _| (*ship_id, *at, *strength));
+
+        while system.handle_one() {};
+        system.send(ScanPulse(ShipId(1), from, MINER_SCANNER));
+
+        ticks.run(&mut system, (MINER_SCANNER.timeout().as_millis() / 100) as usize);
+
+        rx.try_iter().collect()
+    }
+
+    #[test]
+    fn only_strong_enough_pings_in_range_come_back() {
+        // Close by, out of range, and in range but too small to be picked up.
+        let pings = scan(Point3::new(0.0, 50.0, 0.0), &[
+            AsteroidCreated(Point3::new(30.0, 50.0, 0.0), 1.0),
+            AsteroidCreated(Point3::new(200.0, 50.0, 0.0), 2.0),
+            AsteroidCreated(Point3::new(0.0, 170.0, 0.0), 0.5),
+        ]);
+
+        assert_eq!(pings.len(), 1);
+        assert_eq!((pings[0].0, pings[0].1), (ShipId(1), Point3::new(30.0, 50.0, 0.0)));
+        assert!((pings[0].2 - 1.0 / 900.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn asteroids_behind_the_station_or_other_asteroids_are_hidden() {
+        // Straight through the ring, behind a bigger asteroid, and off to the side in plain sight.
+        let pings = scan(Point3::new(-40.0, 0.0, 0.0), &[
+            AsteroidCreated(Point3::new(40.0, 0.0, 0.0), 2.0),
+            AsteroidCreated(Point3::new(-40.0, 0.0, 20.0), 2.0),
+            AsteroidCreated(Point3::new(-40.0, 0.0, 40.0), 1.0),
+            AsteroidCreated(Point3::new(-40.0, 40.0, 0.0), 1.0),
+        ]);
+
+        let mut seen = pings.iter().map(|(_, at, _)| *at).collect::<Vec<_>>();
+        seen.sort_by(|a, b| a.z.partial_cmp(&b.z).unwrap());
+        assert_eq!(seen, vec![Point3::new(-40.0, 40.0, 0.0), Point3::new(-40.0, 0.0, 20.0)]);
+    }
+
+    #[test]
+    fn a_restarted_movement_controller_keeps_the_ship_where_it_was() {
+        struct Explode;
+
+        let (mut system, mut ticks) = test_system(Duration::from_millis(100));
+
+        let start = Point3::new(0.0, 100.0, 0.0);
+        system.send(ShipCreated(ShipId(0), Isometry3::translation(start.x, start.y, start.z)));
+
+        #[cfg(feature = "physics")]
+        crate::physics::init_physics(&mut system);
+
+        system.create_actor(ship_movement_controller(ShipId(0), start)
+            .with_handler(|_, _: &Explode, _| panic!("Boom!"))
+            .build());
+
+        let rx = collect(&mut system, |ShipMoved(_, to), _| Point3::from(to.translation.vector));
+
+        system.send(ShipDestination(ShipId(0), Point3::new(100.0, 100.0, 0.0)));
+
+        ticks.run(&mut system, 20);
+        let before = rx.try_iter().last().unwrap();
+        assert!(before.x > 5.0, "{}", before);
+
+        system.send(Explode);
+        ticks.run(&mut system, 1);
+        let after = rx.try_iter().last().unwrap();
+        assert!((after - before).norm() < 1.0 && after.x >= before.x, "{} went back to {}", before, after);
+    }
+
+    #[test]
+    fn transponders_broadcast_where_the_ship_is_every_100_ticks() {
+        let (mut system, mut ticks) = test_system(Duration::from_millis(100));
+
+        create_transponder(&mut system, ShipId(0), "Barge".to_string(), Point3::new(1.0, 2.0, 3.0));
+        let rx = collect(&mut system, |TransponderBroadcast(id, at), _| (id.clone(), *at));
+
+        let mut received = vec![];
+        for tick in 1..=250 {
+            if tick == 150 {
+                system.send(ShipMoved(ShipId(0), Isometry3::translation(4.0, 5.0, 6.0)));
+            }
+            ticks.run(&mut system, 1);
+            received.extend(rx.try_iter().map(|(id, at)| (tick, id, at)));
+        }
+
+        assert_eq!(received, vec![
+            (100, "Barge".to_string(), Point3::new(1.0, 2.0, 3.0)),
+            (200, "Barge".to_string(), Point3::new(4.0, 5.0, 6.0)),
+        ]);
+    }
+
+    #[cfg(feature = "render")]
+    #[test]
+    fn the_pirate_keeps_flying_around_the_station() {
+        use crate::game::{init_fighter_and_pirate, PIRATE, TIMESTEP};
+
+        let (mut system, mut ticks) = test_system(TIMESTEP);
+
+        #[cfg(feature = "physics")]
+        crate::physics::init_physics(&mut system);
+
+        init_fighter_and_pirate(&mut system);
+        let rx = collect(&mut system, |ShipMoved(id, to), _| (*id, Point3::from(to.translation.vector)));
+
+        // A minute of flying.
+        ticks.run(&mut system, 3600);
+
+        let pirate = rx.try_iter().filter(|(id, _)| *id == PIRATE).map(|(_, at)| at).collect::<Vec<_>>();
+        let furthest = pirate.iter().map(|at| at.coords.norm()).fold(0.0, f64::max);
+        let last_second = (pirate[pirate.len() - 1] - pirate[pirate.len() - 61]).norm();
+
+        assert!(furthest < 450.0, "{}", furthest);
+        assert!((last_second - 60.0).abs() < 10.0, "{}", last_second);
+    }
+}
@@ -0,0 +1,359 @@
+//! Turns raw input from the window into the actions of the game, by a table of bindings
+//! that players can edit.
+//!
+//! The main loop sends the events of every frame in as one FrameInput, which is what gets
+//! recorded, and the input mapper passes them on as RawInputs. It holds on to what is pressed,
+//! and sends an action message, such as Pitch or FireScan, whenever the bindings say an action changed.
+
+use kiss3d::event::{Action, Key, MouseButton};
+use serde::de::IntoDeserializer;
+use serde::Deserialize;
+
+use voyagers_core::actors::{ActorBuilder, System, SystemInterface};
+use voyagers_core::actors::Fate::Keep;
+use voyagers_core::replay::Recordable;
+
+/// The bindings used when there is no bindings file, which double as an example of one.
+pub const DEFAULT_BINDINGS: &str = include_str!("../bindings.txt");
+
+/// A key, mouse button, cursor or scroll wheel event, as sent from outside the system.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum RawInput {
+    Key(Key, Action),
+    Mouse(MouseButton, Action),
+    /// Where the cursor is relative to the centre of the window, from -1 to 1 across it, with y pointing down.
+    Cursor(f64, f64),
+    /// Steps the scroll wheel turned, positive away from the player.
+    Scroll(f64),
+}
+
+/// The RawInput of one frame, in the order it happened.
+///
+/// The main loop sends one every frame, even an empty one: sending it from outside reads the clock,
+/// so game time moves on without input, and a replay sees the same number of messages per frame.
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct FrameInput(pub Vec<RawInput>);
+
+/// Something that can be held down, and so be part of a binding.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Input {
+    Key(Key),
+    Mouse(MouseButton),
+}
+
+/// Parse the name of a variant of one of the kiss3d event enums, which are all Deserialize.
+fn parse_variant<'a, T: Deserialize<'a>>(name: &'a str) -> Option<T> {
+    T::deserialize(IntoDeserializer::<serde::de::value::Error>::into_deserializer(name)).ok()
+}
+
+impl Input {
+    /// Keys go by their kiss3d names, such as Space or LControl, and mouse buttons as Button1 to Button8.
+    fn parse(name: &str) -> Option<Input> {
+        parse_variant(name).map(Input::Key).or_else(|| parse_variant(name).map(Input::Mouse))
+    }
+}
+
+impl Recordable for RawInput {
+    fn encode(&self) -> String {
+        match self {
+            RawInput::Key(key, action) => format!("Key {:?} {:?}", key, action),
+            RawInput::Mouse(button, action) => format!("Mouse {:?} {:?}", button, action),
+            RawInput::Cursor(x, y) => format!("Cursor {} {}", x, y),
+            RawInput::Scroll(steps) => format!("Scroll {}", steps),
+        }
+    }
+
+    fn decode(text: &str) -> Option<Self> {
+        let mut words = text.split(' ');
+        let input = match (words.next()?, words.next()?) {
+            ("Key", key) => RawInput::Key(parse_variant(key)?, parse_variant(words.next()?)?),
+            ("Mouse", button) => RawInput::Mouse(parse_variant(button)?, parse_variant(words.next()?)?),
+            ("Cursor", x) => RawInput::Cursor(x.parse().ok()?, words.next()?.parse().ok()?),
+            ("Scroll", steps) => RawInput::Scroll(steps.parse().ok()?),
+            _ => return None,
+        };
+        words.next().is_none().then_some(input)
+    }
+}
+
+impl Recordable for FrameInput {
+    fn encode(&self) -> String {
+        self.0.iter().map(RawInput::encode).collect::<Vec<_>>().join(", ")
+    }
+
+    fn decode(text: &str) -> Option<Self> {
+        if text.is_empty() {
+            return Some(FrameInput::default());
+        }
+        text.split(", ").map(RawInput::decode).collect::<Option<_>>().map(FrameInput)
+    }
+}
+
+/// Fire the main engine at a fraction of full thrust, regardless of the throttle.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Thrust(pub f64);
+
+/// Steer around the x axis of the ship, from -1 to 1.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Pitch(pub f64);
+
+/// Steer around the y axis of the ship, from -1 to 1.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Yaw(pub f64);
+
+/// Steer around the z axis of the ship, from -1 to 1.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Roll(pub f64);
+
+/// Open (positive) or close (negative) the throttle, at a fraction of the fastest rate.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct MoveThrottle(pub f64);
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct FireScan;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct ToggleFlightAssist;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct TogglePause;
+
+/// Switch between steering by the Pitch and Yaw actions and steering with the mouse.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct ToggleMouseSteering;
+
+/// What a binding can do. Axes hold a value as long as their bindings are held, buttons go off once when pressed.
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub enum Control {
+    Thrust,
+    Pitch,
+    Yaw,
+    Roll,
+    MoveThrottle,
+    FireScan,
+    ToggleFlightAssist,
+    TogglePause,
+    ToggleMouseSteering,
+}
+
+impl Control {
+    const ALL: [Control; 9] = [Control::Thrust, Control::Pitch, Control::Yaw, Control::Roll, Control::MoveThrottle,
+        Control::FireScan, Control::ToggleFlightAssist, Control::TogglePause, Control::ToggleMouseSteering];
+
+    fn parse(name: &str) -> Option<Control> {
+        Control::ALL.iter().copied().find(|control| format!("{:?}", control) == name)
+    }
+
+    fn is_axis(self) -> bool {
+        matches!(self, Control::Thrust | Control::Pitch | Control::Yaw | Control::Roll | Control::MoveThrottle)
+    }
+}
+
+/// A chord of inputs, all of which have to be held for the binding to be active.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Binding {
+    pub control: Control,
+    pub chord: Vec<Input>,
+    /// What an active binding adds to the value of an axis.
+    pub scale: f64,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct Bindings(pub Vec<Binding>);
+
+impl Bindings {
+    /// Parse a bindings file, with lines like `Pitch = S * -1` or `FireScan = LControl + Space`.
+    /// Everything after a # is a comment.
+    pub fn from_text(text: &str) -> Result<Bindings, String> {
+        text.lines().enumerate()
+            .map(|(i, line)| (i, line.split('#').next().unwrap().trim()))
+            .filter(|(_, line)| !line.is_empty())
+            .map(|(i, line)| Binding::parse(line).ok_or_else(|| format!("Line {} of the bindings is not a binding: {}", i + 1, line)))
+            .collect::<Result<_, _>>()
+            .map(Bindings)
+    }
+
+    /// The bindings that are active while the given inputs are held. A binding whose chord is
+    /// part of the chord of another active binding is not, so that LControl + Space does not
+    /// also do what Space does.
+    fn active(&self, held: &[Input]) -> Vec<usize> {
+        let complete = |binding: &Binding| binding.chord.iter().all(|input| held.contains(input));
+
+        (0..self.0.len())
+            .filter(|i| complete(&self.0[*i]))
+            .filter(|i| !self.0.iter().any(|other| {
+                complete(other) && other.chord.len() > self.0[*i].chord.len() && self.0[*i].chord.iter().all(|input| other.chord.contains(input))
+            }))
+            .collect()
+    }
+}
+
+impl Default for Bindings {
+    fn default() -> Self {
+        Bindings::from_text(DEFAULT_BINDINGS).expect("The default bindings do not parse.")
+    }
+}
+
+impl Binding {
+    fn parse(line: &str) -> Option<Binding> {
+        let (control, inputs) = line.split_once('=')?;
+        let (inputs, scale) = match inputs.split_once('*') {
+            Some((inputs, scale)) => (inputs, scale.trim().parse().ok()?),
+            None => (inputs, 1.0),
+        };
+
+        Some(Binding {
+            control: Control::parse(control.trim())?,
+            chord: inputs.split('+').map(|input| Input::parse(input.trim())).collect::<Option<_>>()?,
+            scale,
+        })
+    }
+}
+
+struct InputMapperState {
+    bindings: Bindings,
+    held: Vec<Input>,
+    active: Vec<usize>,
+    axes: Vec<(Control, f64)>,
+}
+
+fn send_control(outbox: &mut SystemInterface, control: Control, value: f64) {
+    match control {
+        Control::Thrust => outbox.send(Thrust(value)),
+        Control::Pitch => outbox.send(Pitch(value)),
+        Control::Yaw => outbox.send(Yaw(value)),
+        Control::Roll => outbox.send(Roll(value)),
+        Control::MoveThrottle => outbox.send(MoveThrottle(value)),
+        Control::FireScan => outbox.send(FireScan),
+        Control::ToggleFlightAssist => outbox.send(ToggleFlightAssist),
+        Control::TogglePause => outbox.send(TogglePause),
+        Control::ToggleMouseSteering => outbox.send(ToggleMouseSteering),
+    }
+}
+
+/// Start the actor that turns RawInput into action messages according to the bindings,
+/// passing on that of every FrameInput as it goes.
+///
+/// Axis actions are sent whenever their value changes, clamped to between -1 and 1.
+/// Button actions are sent once for each of their bindings that becomes active.
+pub fn init_input_mapper(system: &mut System, bindings: Bindings) {
+    let axes = Control::ALL.iter().filter(|control| control.is_axis()).map(|control| (*control, 0.0)).collect();
+
+    system.create_actor(ActorBuilder::new(InputMapperState { bindings, held: vec![], active: vec![], axes })
+        .with_handler(|_, FrameInput(inputs), outbox| {
+            for input in inputs {
+                outbox.send(*input);
+            }
+            Keep
+        })
+        .with_handler(|state, raw: &RawInput, outbox| {
+            let (input, action) = match *raw {
+                RawInput::Key(key, action) => (Input::Key(key), action),
+                RawInput::Mouse(button, action) => (Input::Mouse(button), action),
+                // The cursor and scroll wheel are for mouse steering to read directly.
+                RawInput::Cursor(..) | RawInput::Scroll(..) => return Keep,
+            };
+
+            match action {
+                Action::Press if !state.held.contains(&input) => state.held.push(input),
+                Action::Release => state.held.retain(|held| *held != input),
+                _ => {}
+            }
+
+            let active = state.bindings.active(&state.held);
+
+            for i in &active {
+                let binding = &state.bindings.0[*i];
+                if !binding.control.is_axis() && !state.active.contains(i) {
+                    send_control(outbox, binding.control, 1.0);
+                }
+            }
+
+            let bindings = &state.bindings;
+            for (control, value) in state.axes.iter_mut() {
+                let new_value = active.iter()
+                    .map(|i| &bindings.0[*i])
+                    .filter(|binding| binding.control == *control)
+                    .fold(0.0, |sum, binding| sum + binding.scale)
+                    .clamp(-1.0, 1.0);
+
+                if new_value != *value {
+                    *value = new_value;
+                    send_control(outbox, *control, new_value);
+                }
+            }
+
+            state.active = active;
+
+            Keep
+        }).build());
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::mpsc::channel;
+
+    use kiss3d::event::{Action, Key, MouseButton};
+
+    use voyagers_core::actors::{ActorBuilder, System};
+    use voyagers_core::actors::Fate::Keep;
+    use voyagers_core::replay::Recordable;
+
+    use crate::input::{Bindings, FireScan, FrameInput, init_input_mapper, Pitch, RawInput, Thrust};
+
+    #[test]
+    fn chords_and_axes() {
+        let mut system = System::new();
+
+        let bindings = Bindings::from_text("
+            # Comments and blank lines are fine.
+            Thrust = Space
+            Pitch = W
+            Pitch = S * -1
+            FireScan = LControl + Space
+            FireScan = Button1
+        ").unwrap();
+        init_input_mapper(&mut system, bindings);
+
+        let (tx, rx) = channel();
+        let (tx2, tx3) = (tx.clone(), tx.clone());
+        system.create_actor(ActorBuilder::new(())
+            .with_handler(move |_, Thrust(value), _| {
+                tx.send(format!("Thrust {}", value)).unwrap();
+                Keep
+            })
+            .with_handler(move |_, Pitch(value), _| {
+                tx2.send(format!("Pitch {}", value)).unwrap();
+                Keep
+            })
+            .with_handler(move |_, _: &FireScan, _| {
+                tx3.send("FireScan".to_string()).unwrap();
+                Keep
+            }).build());
+
+        for frame in [
+            vec![RawInput::Key(Key::W, Action::Press)],
+            vec![RawInput::Key(Key::S, Action::Press), RawInput::Key(Key::S, Action::Release)],
+            vec![],
+            vec![RawInput::Key(Key::Space, Action::Press)],
+            vec![RawInput::Key(Key::LControl, Action::Press), RawInput::Key(Key::LControl, Action::Release)],
+            vec![RawInput::Mouse(MouseButton::Button1, Action::Press), RawInput::Cursor(0.25, -0.5), RawInput::Scroll(-1.0)],
+        ] {
+            // What goes in goes through a recording unchanged.
+            let frame = FrameInput(frame);
+            assert_eq!(FrameInput::decode(&frame.encode()), Some(frame.clone()));
+            system.send(frame);
+            while system.handle_one() {};
+        }
+
+        assert_eq!(rx.try_iter().collect::<Vec<_>>(), vec![
+            "Pitch 1", "Pitch 0", "Pitch 1", "Thrust 1",
+            // Holding LControl + Space scans instead of thrusting.
+            "FireScan", "Thrust 0", "Thrust 1",
+            "FireScan",
+        ]);
+
+        assert!(Bindings::from_text("Pitch = NotAKey").is_err());
+        Bindings::default();
+    }
+}
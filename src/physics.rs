@@ -0,0 +1,464 @@
+//! Rigid-body physics for ships and asteroids, simulated by rapier3d.
+//!
+//! The rest of the game works in f64 nalgebra types, while rapier uses f32 and its own
+//! version of nalgebra, so poses and vectors are converted at the edges of this module.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use nalgebra::{Isometry3, Point3, Quaternion, Translation3, UnitQuaternion, Vector3};
+use rapier3d::prelude as rp;
+
+use voyagers_core::actors::Fate::Keep;
+use voyagers_core::actors::{Keyed, System};
+use voyagers_core::clock::Tick;
+use voyagers_core::parallel::SendActorBuilder;
+
+use crate::game::{AsteroidCollected, AsteroidCreated, ScanPulse, ShipCreated, ShipId, ShipMoved, STATION_SPIN};
+
+/// Radius of the collision sphere of a ship.
+pub const SHIP_RADIUS: f64 = 0.5;
+
+/// Mass of a ship, so that controllers can turn accelerations into thrust.
+pub const SHIP_MASS: f64 = 1.0;
+
+/// The ringstation collides with its actual shape, so it is loaded from its model.
+const STATION_MODEL: &str = "models/ringstation.obj";
+
+/// Force in newtons on a ship, in the ship's own frame, kept up until the next ShipThrust.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct ShipThrust(pub ShipId, pub Vector3<f64>);
+
+impl Keyed for ShipThrust {
+    type Key = ShipId;
+
+    fn key(&self) -> &ShipId {
+        &self.0
+    }
+}
+
+/// Torque on a ship, in the ship's own frame, kept up until the next ShipTorque.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct ShipTorque(pub ShipId, pub Vector3<f64>);
+
+impl Keyed for ShipTorque {
+    type Key = ShipId;
+
+    fn key(&self) -> &ShipId {
+        &self.0
+    }
+}
+
+/// Linear and angular velocity of a ship in the world frame, published along with its ShipMoved after every step.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct ShipVelocity(pub ShipId, pub Vector3<f64>, pub Vector3<f64>);
+
+impl Keyed for ShipVelocity {
+    type Key = ShipId;
+
+    fn key(&self) -> &ShipId {
+        &self.0
+    }
+}
+
+/// Something with a body in the physics world.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Entity {
+    Ship(ShipId),
+    /// Asteroids are told apart by where they were created.
+    Asteroid(Point3<f64>),
+    Station,
+}
+
+/// Two entities started touching, and were pushed apart with the given impulse in their first step of contact.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct CollisionStarted(pub Entity, pub Entity, pub f64);
+
+/// Two entities stopped touching.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct CollisionEnded(pub Entity, pub Entity);
+
+/// The asteroids in range of a scan pulse that the straight line from where it was sent
+/// reaches before anything else.
+#[derive(Clone)]
+pub struct AsteroidsInSight(pub ScanPulse, pub Vec<Point3<f64>>);
+
+/// Collects the contact events of a step, for the physics actor to send on afterwards.
+#[derive(Default)]
+struct ContactEvents(Mutex<Vec<rp::ContactEvent>>);
+
+impl rp::EventHandler for ContactEvents {
+    fn handle_intersection_event(&self, _: rp::IntersectionEvent) {}
+
+    fn handle_contact_event(&self, event: rp::ContactEvent, _: &rp::ContactPair) {
+        self.0.lock().unwrap().push(event);
+    }
+}
+
+struct Ship {
+    id: ShipId,
+    body: rp::RigidBodyHandle,
+    thrust: Vector3<f64>,
+    torque: Vector3<f64>,
+}
+
+struct PhysicsWorld {
+    pipeline: rp::PhysicsPipeline,
+    parameters: rp::IntegrationParameters,
+    islands: rp::IslandManager,
+    broad_phase: rp::BroadPhase,
+    narrow_phase: rp::NarrowPhase,
+    bodies: rp::RigidBodySet,
+    colliders: rp::ColliderSet,
+    joints: rp::JointSet,
+    ccd_solver: rp::CCDSolver,
+    query_pipeline: rp::QueryPipeline,
+    ships: Vec<Ship>,
+    asteroids: Vec<(Point3<f64>, rp::RigidBodyHandle)>,
+    entities: HashMap<rp::ColliderHandle, Entity>,
+    /// Pairs in contact that have not pushed each other apart yet.
+    approaching: Vec<(rp::ColliderHandle, rp::ColliderHandle)>,
+}
+
+impl PhysicsWorld {
+    fn new() -> Self {
+        PhysicsWorld {
+            pipeline: rp::PhysicsPipeline::new(),
+            parameters: rp::IntegrationParameters::default(),
+            islands: rp::IslandManager::new(),
+            broad_phase: rp::BroadPhase::new(),
+            narrow_phase: rp::NarrowPhase::new(),
+            bodies: rp::RigidBodySet::new(),
+            colliders: rp::ColliderSet::new(),
+            joints: rp::JointSet::new(),
+            ccd_solver: rp::CCDSolver::new(),
+            query_pipeline: rp::QueryPipeline::new(),
+            ships: vec![],
+            asteroids: vec![],
+            entities: HashMap::new(),
+            approaching: vec![],
+        }
+    }
+
+    fn insert(&mut self, body: rp::RigidBody, collider: rp::Collider, entity: Entity) -> rp::RigidBodyHandle {
+        let handle = self.bodies.insert(body);
+        let collider = self.colliders.insert_with_parent(collider, handle, &mut self.bodies);
+        self.entities.insert(collider, entity);
+        handle
+    }
+
+    fn remove(&mut self, handle: rp::RigidBodyHandle) {
+        for collider in self.bodies[handle].colliders() {
+            self.entities.remove(collider);
+        }
+        self.bodies.remove(handle, &mut self.islands, &mut self.colliders, &mut self.joints);
+    }
+
+    /// The asteroids within range of a point that a ray from there hits before anything else,
+    /// passing through the ship at that point.
+    fn asteroids_in_sight(&mut self, from: Point3<f64>, range: f64, ship: ShipId) -> Vec<Point3<f64>> {
+        self.query_pipeline.update(&self.islands, &self.bodies, &self.colliders);
+
+        let entities = &self.entities;
+        let other_than_ship = |collider| entities.get(&collider) != Some(&Entity::Ship(ship));
+        let origin = rp::Point::new(from.x as f32, from.y as f32, from.z as f32);
+
+        self.asteroids.iter()
+            .map(|(at, _)| *at)
+            .filter(|at| (at - from).norm() <= range)
+            .filter(|at| {
+                // Cast all the way to the centre, so that the asteroid itself is hit if nothing is in front of it.
+                let ray = rp::Ray::new(origin, vector_to_rapier(&(at - from)));
+                let hit = self.query_pipeline.cast_ray(&self.colliders, &ray, 1.0, true, rp::InteractionGroups::all(), Some(&other_than_ship));
+                hit.is_none_or(|(collider, _)| entities.get(&collider) == Some(&Entity::Asteroid(*at)))
+            })
+            .collect()
+    }
+
+    /// Advance the world by the dt of the tick, and return the collisions that started and ended.
+    fn step(&mut self, tick: &Tick) -> (Vec<CollisionStarted>, Vec<CollisionEnded>) {
+        for ship in &self.ships {
+            let body = &mut self.bodies[ship.body];
+            let rotation = from_rapier(body.position()).rotation;
+            body.apply_force(vector_to_rapier(&(rotation * ship.thrust)), true);
+            body.apply_torque(vector_to_rapier(&(rotation * ship.torque)), true);
+        }
+
+        self.parameters.dt = tick.dt.as_secs_f32();
+
+        let events = ContactEvents::default();
+
+        // Space has no gravity.
+        self.pipeline.step(&rp::Vector::zeros(),
+                           &self.parameters,
+                           &mut self.islands,
+                           &mut self.broad_phase,
+                           &mut self.narrow_phase,
+                           &mut self.bodies,
+                           &mut self.colliders,
+                           &mut self.joints,
+                           &mut self.ccd_solver,
+                           &(),
+                           &events);
+
+        let mut ended = vec![];
+
+        for event in events.0.into_inner().unwrap() {
+            match event {
+                rp::ContactEvent::Started(a, b) => self.approaching.push((a, b)),
+                rp::ContactEvent::Stopped(a, b) => {
+                    // Pairs that never pushed each other apart only came close, so they never started either.
+                    if let Some(i) = self.approaching.iter().position(|pair| *pair == (a, b)) {
+                        self.approaching.swap_remove(i);
+                    } else if let (Some(a), Some(b)) = (self.entities.get(&a), self.entities.get(&b)) {
+                        // Either may have been removed since, in which case it is no news that they no longer touch.
+                        ended.push(CollisionEnded(*a, *b));
+                    }
+                }
+            }
+        }
+
+        // Contacts start a little before the colliders touch, so wait for the solver to push them apart.
+        let mut started = vec![];
+        let (narrow_phase, entities) = (&self.narrow_phase, &self.entities);
+        self.approaching.retain(|(a, b)| {
+            let impulse: f32 = narrow_phase.contact_pair(*a, *b).map_or(0.0, |pair| {
+                pair.manifolds.iter().flat_map(|manifold| &manifold.points).map(|point| point.data.impulse).sum()
+            });
+            match (entities.get(a), entities.get(b)) {
+                (Some(a), Some(b)) if impulse > 0.0 => {
+                    started.push(CollisionStarted(*a, *b, impulse as f64));
+                    false
+                }
+                (Some(_), Some(_)) => true,
+                _ => false,
+            }
+        });
+
+        (started, ended)
+    }
+}
+
+/// Read the triangles of a Wavefront OBJ file, ignoring everything but the vertex positions.
+fn load_trimesh(path: &str) -> rp::Collider {
+    let text = std::fs::read_to_string(path).unwrap_or_else(|e| panic!("Could not read {}: {}", path, e));
+
+    let mut vertices = vec![];
+    let mut indices = vec![];
+
+    for line in text.lines() {
+        let mut words = line.split_whitespace();
+        match words.next() {
+            Some("v") => {
+                let coords: Vec<f32> = words.map(|w| w.parse().expect("Bad vertex in model.")).collect();
+                vertices.push(rp::Point::new(coords[0], coords[1], coords[2]));
+            }
+            Some("f") => {
+                // Faces refer to their vertices counting from one, as "v/vt/vn".
+                let corners: Vec<u32> = words.map(|w| w.split('/').next().unwrap().parse::<u32>().expect("Bad face in model.") - 1).collect();
+                for i in 1..corners.len() - 1 {
+                    indices.push([corners[0], corners[i], corners[i + 1]]);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    rp::ColliderBuilder::trimesh(vertices, indices).build()
+}
+
+fn to_rapier(pose: &Isometry3<f64>) -> rp::Isometry<rp::Real> {
+    let rotation = pose.rotation.cast::<f32>();
+    rp::Isometry::from_parts(
+        rp::Translation::new(pose.translation.x as f32, pose.translation.y as f32, pose.translation.z as f32),
+        rp::Rotation::new_unchecked(rp::nalgebra::Quaternion::new(rotation.w, rotation.i, rotation.j, rotation.k)),
+    )
+}
+
+fn from_rapier(pose: &rp::Isometry<rp::Real>) -> Isometry3<f64> {
+    let rotation = pose.rotation;
+    Isometry3::from_parts(
+        Translation3::new(pose.translation.x, pose.translation.y, pose.translation.z).cast(),
+        UnitQuaternion::new_unchecked(Quaternion::new(rotation.w, rotation.i, rotation.j, rotation.k).cast()),
+    )
+}
+
+fn vector_to_rapier(v: &Vector3<f64>) -> rp::Vector<rp::Real> {
+    rp::Vector::new(v.x as f32, v.y as f32, v.z as f32)
+}
+
+fn vector_from_rapier(v: &rp::Vector<rp::Real>) -> Vector3<f64> {
+    Vector3::new(v.x, v.y, v.z).cast()
+}
+
+/// Start the actor that owns the physics world.
+///
+/// Ships get a body when a ShipCreated is sent, and are pushed around by ShipThrust and
+/// ShipTorque. Asteroids get a fixed body when created, which goes away once collected.
+/// The ringstation sits at the origin, spinning like it is drawn.
+/// A ScanPulse is answered with the AsteroidsInSight of it, hidden neither by the ringstation nor other asteroids.
+/// Every Tick advances the world by its dt, then sends a ShipMoved and a ShipVelocity per ship,
+/// and a CollisionStarted or CollisionEnded for every pair of entities that started or stopped touching.
+pub fn init_physics(system: &mut System) {
+    let mut world = PhysicsWorld::new();
+
+    let station = rp::RigidBodyBuilder::new_kinematic_velocity_based()
+        .angvel(rp::Vector::y() * STATION_SPIN as f32)
+        .build();
+    world.insert(station, load_trimesh(STATION_MODEL), Entity::Station);
+
+    system.create_send_actor(SendActorBuilder::new(world)
+        .with_handler(|world, ShipCreated(id, at), _| {
+            // Undamped, so that ships keep turning until their flight model stops them.
+            let body = rp::RigidBodyBuilder::new_dynamic()
+                .position(to_rapier(at))
+                .build();
+            // Ships report their contacts with anything, which covers every pair that can touch.
+            let collider = rp::ColliderBuilder::ball(SHIP_RADIUS as f32)
+                .density((SHIP_MASS / (4.0 / 3.0 * std::f64::consts::PI * SHIP_RADIUS.powi(3))) as f32)
+                .active_events(rp::ActiveEvents::CONTACT_EVENTS)
+                .build();
+            let body = world.insert(body, collider, Entity::Ship(*id));
+            world.ships.push(Ship { id: *id, body, thrust: Vector3::zeros(), torque: Vector3::zeros() });
+            Keep
+        })
+        .with_handler(|world, ShipThrust(id, thrust), _| {
+            if let Some(ship) = world.ships.iter_mut().find(|ship| ship.id == *id) {
+                ship.thrust = *thrust;
+            }
+            Keep
+        })
+        .with_handler(|world, ShipTorque(id, torque), _| {
+            if let Some(ship) = world.ships.iter_mut().find(|ship| ship.id == *id) {
+                ship.torque = *torque;
+            }
+            Keep
+        })
+        .with_handler(|world, AsteroidCreated(at, radius), _| {
+            let body = rp::RigidBodyBuilder::new_static()
+                .translation(vector_to_rapier(&at.coords))
+                .build();
+            let body = world.insert(body, rp::ColliderBuilder::ball(*radius as f32).build(), Entity::Asteroid(*at));
+            world.asteroids.push((*at, body));
+            Keep
+        })
+        .with_handler(|world, AsteroidCollected(at), _| {
+            if let Some(i) = world.asteroids.iter().position(|(created_at, _)| created_at == at) {
+                let (_, body) = world.asteroids.swap_remove(i);
+                world.remove(body);
+            }
+            Keep
+        })
+        .with_handler(|world, pulse: &ScanPulse, outbox| {
+            let ScanPulse(ship, from, scanner) = pulse;
+            outbox.send(AsteroidsInSight(pulse.clone(), world.asteroids_in_sight(*from, scanner.range, *ship)));
+            Keep
+        })
+        .with_handler(|world, tick: &Tick, outbox| {
+            let (started, ended) = world.step(tick);
+
+            for ship in &world.ships {
+                let body = &world.bodies[ship.body];
+                outbox.send(ShipMoved(ship.id, from_rapier(body.position())));
+                outbox.send(ShipVelocity(ship.id, vector_from_rapier(body.linvel()), vector_from_rapier(body.angvel())));
+            }
+            for collision in started {
+                outbox.send(collision);
+            }
+            for collision in ended {
+                outbox.send(collision);
+            }
+            Keep
+        }).build());
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::mpsc::channel;
+    use std::time::Duration;
+
+    use nalgebra::{Isometry3, Point3, Vector3};
+
+    use voyagers_core::actors::{ActorBuilder, System};
+    use voyagers_core::actors::Fate::Keep;
+    use voyagers_core::clock::FixedTimestep;
+
+    use crate::game::{AsteroidCreated, ShipCreated, ShipId, ShipMoved};
+    use crate::physics::{CollisionEnded, CollisionStarted, Entity, init_physics, ShipThrust};
+
+    #[test]
+    fn thrust_pushes_ships_until_they_hit_an_asteroid() {
+        let mut system = System::new();
+        let mut timestep = FixedTimestep::new(Duration::from_millis(10));
+
+        init_physics(&mut system);
+
+        let (tx, rx) = channel();
+        system.create_actor(ActorBuilder::new(())
+            .with_handler(move |_, ShipMoved(_, at), _| {
+                tx.send(at.translation.vector).unwrap();
+                Keep
+            }).build());
+
+        // Well away from the station at the origin.
+        system.send(AsteroidCreated(Point3::new(50.0, 0.0, -10.0), 1.0));
+        system.send(ShipCreated(ShipId(1), Isometry3::translation(50.0, 0.0, 0.0)));
+        system.send(ShipThrust(ShipId(1), Vector3::new(0.0, 0.0, -5.0)));
+
+        for _ in 0..500 {
+            system.send(timestep.step());
+            while system.handle_one() {};
+        }
+
+        let positions = rx.try_iter().collect::<Vec<_>>();
+        assert!(positions[1].z < positions[0].z);
+        // The ship stops against the asteroid instead of flying through it.
+        assert!(positions.iter().all(|at| at.z > -9.0), "{:?}", positions.last());
+    }
+
+    #[test]
+    fn collisions_are_reported() {
+        let mut system = System::new();
+        let mut timestep = FixedTimestep::new(Duration::from_millis(10));
+
+        init_physics(&mut system);
+
+        let (tx, rx) = channel();
+        let ended_tx = tx.clone();
+        system.create_actor(ActorBuilder::new(())
+            .with_handler(move |_, CollisionStarted(a, b, impulse), _| {
+                tx.send((*a, *b, Some(*impulse))).unwrap();
+                Keep
+            })
+            .with_handler(move |_, CollisionEnded(a, b), _| {
+                ended_tx.send((*a, *b, None)).unwrap();
+                Keep
+            }).build());
+
+        // Two ships flying at each other bounce apart.
+        system.send(ShipCreated(ShipId(1), Isometry3::translation(50.0, 0.0, 0.0)));
+        system.send(ShipCreated(ShipId(2), Isometry3::translation(60.0, 0.0, 0.0)));
+        system.send(ShipThrust(ShipId(1), Vector3::new(10.0, 0.0, 0.0)));
+        system.send(ShipThrust(ShipId(2), Vector3::new(-10.0, 0.0, 0.0)));
+
+        for _ in 0..100 {
+            system.send(timestep.step());
+            while system.handle_one() {};
+        }
+
+        system.send(ShipThrust(ShipId(1), Vector3::new(-10.0, 0.0, 0.0)));
+        system.send(ShipThrust(ShipId(2), Vector3::new(10.0, 0.0, 0.0)));
+
+        for _ in 0..100 {
+            system.send(timestep.step());
+            while system.handle_one() {};
+        }
+
+        let collisions = rx.try_iter().collect::<Vec<_>>();
+        assert_eq!(collisions.len(), 2, "{:?}", collisions);
+
+        let ships = |a, b| matches!((a, b), (Entity::Ship(ShipId(1)), Entity::Ship(ShipId(2))) | (Entity::Ship(ShipId(2)), Entity::Ship(ShipId(1))));
+        assert!(matches!(collisions[0], (a, b, Some(impulse)) if ships(a, b) && impulse > 0.0), "{:?}", collisions);
+        assert!(matches!(collisions[1], (a, b, None) if ships(a, b)), "{:?}", collisions);
+    }
+}
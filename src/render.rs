@@ -0,0 +1,505 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::ops::DerefMut;
+use std::path::Path;
+use std::rc::Rc;
+use std::time::Instant;
+
+use kiss3d::camera::{ArcBall, Camera};
+use kiss3d::event::WindowEvent;
+use kiss3d::scene::SceneNode;
+use kiss3d::window::Window;
+use nalgebra::{Point3, Translation3, UnitQuaternion, Vector3};
+
+use voyagers_core::actors::{ActorBuilder, ActorData, System, SystemInterface};
+use voyagers_core::actors::Fate::{Become, End, Keep};
+use voyagers_core::clock::{FixedTimestep, Tick};
+
+use crate::flight::{FlightAssist, Steer, Throttle};
+use crate::game::{AsteroidCollected, AsteroidCreated, FIGHTER, FIGHTER_SCANNER, MINING_SHIPS, PIRATE, ScanPulse, ShipId, ShipMoved, STATION_SPIN, TIMESTEP};
+use crate::input::{Bindings, FireScan, FrameInput, init_input_mapper, MoveThrottle, Pitch, RawInput, Roll, Thrust, ToggleFlightAssist, ToggleMouseSteering, TogglePause, Yaw};
+
+/// Sent once per rendered frame, with how far it is between the last Tick and the next, from 0 to 1.
+///
+/// Only affects what is shown, so it is sent from within the system rather than recorded.
+pub struct Frame(pub f64);
+
+fn create_ship_tracking_widget(system: &mut System, fighter_ship_id: ShipId, radar_sn: SceneNode) {
+    system.create_actor(ActorBuilder::new(TrackerVizState {
+        positions: Default::default(),
+        sn: radar_sn,
+    })
+        .with_handler(move |st, ShipMoved(id, at), _| {
+            if *id != fighter_ship_id {
+                if st.positions.contains_key(id) {
+                    st.positions.get_mut(id).unwrap().1 = at.translation.vector.into();
+                } else {
+                    st.positions.insert(*id, ({
+                                                  let mut sn = st.sn.add_sphere(0.1);
+                                                  sn.set_color(0.0, 1.0, 1.0);
+                                                  sn
+                                              }, at.translation.vector.into()));
+                }
+            } else {
+                st.sn.set_local_translation(at.translation.cast());
+            }
+
+            Keep
+        }).with_handler(move |st, _: &Tick, _| {
+        let base_frame: Point3<f32> = st.sn.data().local_translation().vector.into();
+
+        for (_, (sn, pos)) in st.positions.iter_mut() {
+            sn.set_local_translation(
+                ((pos.cast() - base_frame).normalize() * 10.0).into()
+            )
+        }
+
+        Keep
+    }).build());
+}
+
+struct TrackerVizState {
+    positions: HashMap<ShipId, (SceneNode, Point3<f64>)>,
+    sn: SceneNode,
+}
+
+fn delete_scenenode_when_asteroid_collected(system: &mut SystemInterface, roid: Point3<f64>, ball: SceneNode) {
+    system.create_actor(ActorBuilder::new(ball).with_handler(move |ball, AsteroidCollected(the_roid), _| {
+        if &roid == the_roid {
+            ball.unlink();
+            End
+        } else {
+            Keep
+        }
+    })
+        .build());
+}
+
+fn create_followcam_actor(system: &mut SystemInterface, fighter_ship_id: ShipId, camera: &Rc<RefCell<ArcBall>>) {
+    system.create_actor(ActorBuilder::new(camera.clone())
+        .with_keyed_handler(fighter_ship_id, move |cam, ShipMoved(_, at), _| {
+            let eye: Point3<f32> = (*cam).borrow().eye();
+            let focus: Point3<f32> = at.translation.vector.cast().into();
+
+            let new_eye_tgt: Point3<f32> = focus + (eye - focus).normalize() * 20.0;
+
+            let new_eye: Point3<f32> = eye + (new_eye_tgt - eye) * 0.01;
+
+            (**cam).borrow_mut().look_at(new_eye, focus);
+            Keep
+        })
+        .build());
+}
+
+struct FighterControls {
+    ship_id: ShipId,
+    thrust: f64,
+    steer: Vector3<f64>,
+    throttle: f64,
+    moving_throttle: f64,
+    assist: bool,
+}
+
+/// Fly the fighter by the actions of the input mapper, keeping track of the throttle.
+///
+/// It starts out steered by the Pitch and Yaw actions; ToggleMouseSteering switches to and from
+/// steering with the mouse.
+fn create_fighter_controller(system: &mut System, fighter_ship_id: ShipId) {
+    system.create_actor(steered_by_actions(ActorBuilder::new(FighterControls {
+        ship_id: fighter_ship_id,
+        thrust: 0.0,
+        steer: Vector3::zeros(),
+        throttle: 0.0,
+        moving_throttle: 0.0,
+        assist: true,
+    })));
+}
+
+/// Handlers shared by all ways of steering the fighter.
+fn fighter_controls(builder: ActorBuilder<FighterControls>) -> ActorBuilder<FighterControls> {
+    // Fraction of full throttle per second.
+    const THROTTLE_RATE: f64 = 0.5;
+
+    builder
+        .with_handler(|controls, Thrust(thrust), _| {
+            controls.thrust = *thrust;
+            Keep
+        })
+        .with_handler(|controls, Roll(roll), _| {
+            controls.steer.z = *roll;
+            Keep
+        })
+        .with_handler(|controls, MoveThrottle(rate), _| {
+            controls.moving_throttle = *rate;
+            Keep
+        })
+        .with_handler(|controls, _: &ToggleFlightAssist, outbox| {
+            controls.assist = !controls.assist;
+            outbox.send(FlightAssist(controls.ship_id, controls.assist));
+            Keep
+        })
+        .with_handler(|controls, tick: &Tick, outbox| {
+            controls.throttle = (controls.throttle + controls.moving_throttle * THROTTLE_RATE * tick.dt.as_secs_f64()).clamp(0.0, 1.0);
+
+            outbox.send(Throttle(controls.ship_id, controls.throttle.max(controls.thrust)));
+            outbox.send(Steer(controls.ship_id, controls.steer));
+
+            Keep
+        })
+}
+
+fn steered_by_actions(builder: ActorBuilder<FighterControls>) -> ActorData {
+    fighter_controls(builder)
+        .with_handler(|controls, Pitch(pitch), _| {
+            controls.steer.x = *pitch;
+            Keep
+        })
+        .with_handler(|controls, Yaw(yaw), _| {
+            controls.steer.y = *yaw;
+            Keep
+        })
+        .with_handler(|controls, _: &ToggleMouseSteering, _| {
+            controls.steer.x = 0.0;
+            controls.steer.y = 0.0;
+            Become(steered_by_mouse(ActorBuilder::behavior()))
+        }).build()
+}
+
+/// The further the cursor is from the centre of the window, the faster the fighter turns
+/// that way, and the scroll wheel moves the throttle.
+fn steered_by_mouse(builder: ActorBuilder<FighterControls>) -> ActorData {
+    // How far from the centre, out of 1, the cursor can be without turning the ship,
+    // and how much of the throttle one step of the scroll wheel moves.
+    const DEAD_ZONE: f64 = 0.05;
+    const SCROLL_STEP: f64 = 0.05;
+
+    fighter_controls(builder)
+        .with_handler(|controls, raw: &RawInput, _| {
+            match *raw {
+                RawInput::Cursor(x, y) => {
+                    let steer = |offset: f64| if offset.abs() < DEAD_ZONE { 0.0 } else { offset.clamp(-1.0, 1.0) };
+                    // The window's y axis points down, and the ship turns left for a positive yaw.
+                    controls.steer.x = -steer(y);
+                    controls.steer.y = -steer(x);
+                }
+                RawInput::Scroll(steps) => {
+                    controls.throttle = (controls.throttle + steps * SCROLL_STEP).clamp(0.0, 1.0);
+                }
+                _ => {}
+            }
+            Keep
+        })
+        .with_handler(|controls, _: &ToggleMouseSteering, _| {
+            controls.steer.x = 0.0;
+            controls.steer.y = 0.0;
+            Become(steered_by_actions(ActorBuilder::behavior()))
+        }).build()
+}
+
+/// Send a scan pulse out from the fighter when asked to.
+fn create_fighter_scanner(system: &mut System, fighter_ship_id: ShipId) {
+    system.create_actor(ActorBuilder::new(Point3::origin())
+        .with_keyed_handler(fighter_ship_id, |position, ShipMoved(_, at), _| {
+            *position = at.translation.vector.into();
+            Keep
+        })
+        .with_handler(move |position, _: &FireScan, outbox| {
+            outbox.send(ScanPulse(fighter_ship_id, *position, FIGHTER_SCANNER));
+            Keep
+        }).build());
+}
+
+/// Move a scene node along with a ship, in between where it was at the last two Ticks.
+fn follow_ship(system: &mut System, ship_id: ShipId, node: SceneNode) {
+    system.create_actor(ActorBuilder::new((node, None))
+        .with_keyed_handler(ship_id, move |(_, positions), ShipMoved(_, to), _| {
+            let previous = positions.map_or(*to, |(_, current)| current);
+            *positions = Some((previous, *to));
+            Keep
+        })
+        .with_handler(move |(node, positions), Frame(alpha), _| {
+            if let Some((previous, current)) = positions {
+                node.set_local_transformation(previous.lerp_slerp(current, *alpha).cast());
+            }
+            Keep
+        }).build());
+}
+
+/// Send in the input of one frame, then the Ticks that are due since the last frame, and the Frame to draw.
+fn run_frame(system: &mut System, timestep: &mut FixedTimestep, last_frame: &mut Instant, input: FrameInput, workers: usize) {
+    // Sent even when empty, as sending it is what reads the clock.
+    system.send(input);
+    while system.handle_batch(workers) {}
+
+    // Game time stands still while paused, so no Ticks are due then.
+    let now = system.input_interface.now();
+    for tick in timestep.advance(now.saturating_duration_since(*last_frame)) {
+        system.send(tick);
+        while system.handle_batch(workers) {}
+    }
+    *last_frame = now;
+
+    system.input_interface.send(Frame(timestep.alpha()));
+    while system.handle_batch(workers) {}
+}
+
+/// Open a window showing the game, and run it until the window is closed.
+pub fn run_windowed(system: &mut System, workers: usize, bindings: Bindings) {
+    let window = Rc::new(RefCell::new(Window::new_with_size("Kiss3d: cube", 1000, 800)));
+
+    for ship_id in 0..MINING_SHIPS {
+        let miner = (*window).borrow_mut().add_obj(Path::new("models/miner.obj"), Path::new("models"), Vector3::new(1.0, 1.0, 1.0));
+
+        follow_ship(system, ShipId(ship_id), miner);
+    }
+
+    let mut fighter = (*window).borrow_mut().add_obj(Path::new("models/fighter.obj"), Path::new("models"), Vector3::new(1.0, 1.0, 1.0));
+    fighter.set_local_translation(Translation3::new(0.0, 5.0, 30.0));
+
+    let mut pirate_sn = (*window).borrow_mut().add_obj(Path::new("models/fighter.obj"), Path::new("models"), Vector3::new(1.0, 1.0, 1.0));
+    pirate_sn.set_local_translation(Translation3::new(100.0, 0.0, 100.0));
+
+    follow_ship(system, PIRATE, pirate_sn);
+
+    // let mut sn = SceneNode::new_empty();
+    // window.scene_mut().add_child(sn.clone());
+    //
+    // system.create_actor(AActorBuilder:new()
+    //     .with_handler(|_,ScanPulse(origin),outbox| outbox.send(ExpandingSphereEffect(origin.clone())))
+    //     .with_handler(|_,ScanPing(origin),outbox| outbox.send(ExpandingSphereEffect(origin.clone())));
+    //
+    // init_scan_pulse_visualizer(&mut system, sn);
+
+    let ringstation = (*window).borrow_mut().add_obj(Path::new("models/ringstation.obj"), Path::new("models"), Vector3::new(1.0, 1.0, 1.0));
+
+    system.create_actor(ActorBuilder::new(ringstation).with_handler(move |sn, tick: &Tick, _| {
+        sn.append_rotation(&UnitQuaternion::from_axis_angle(&Vector3::y_axis(), (STATION_SPIN * tick.dt.as_secs_f64()) as f32));
+
+        Keep
+    }).build());
+
+    system.create_actor(ActorBuilder::new(window.clone())
+        .with_handler(|window, AsteroidCreated(at, radius), system| {
+            let mut ball = (**window).borrow_mut().add_sphere(*radius as f32);
+            ball.set_local_translation(at.cast().into());
+            delete_scenenode_when_asteroid_collected(system, *at, ball);
+            Keep
+        }).build());
+
+    let camera = kiss3d::camera::ArcBall::new(Point3::new(1.0, 1.0, 1.0), Point3::new(0.0, 5.0, 10.0));
+
+    let camera = Rc::new(RefCell::new(camera));
+
+    init_input_mapper(system, bindings);
+
+    create_fighter_controller(system, FIGHTER);
+
+    create_fighter_scanner(system, FIGHTER);
+
+    follow_ship(system, FIGHTER, fighter);
+
+    create_followcam_actor(&mut system.input_interface, FIGHTER, &camera);
+
+    let radar_sn = SceneNode::new_empty();
+
+    (*window).borrow_mut().scene_mut().add_child(radar_sn.clone());
+
+    create_ship_tracking_widget(system, FIGHTER, radar_sn);
+
+    system.create_actor(ActorBuilder::new(()).with_handler(|_, _: &TogglePause, outbox| {
+        if outbox.is_paused() { outbox.resume() } else { outbox.pause() }
+        Keep
+    }).build());
+
+    let mut timestep = FixedTimestep::new(TIMESTEP);
+    let mut last_frame = system.input_interface.now();
+
+    while (*window).borrow_mut().render_with_camera((*camera).borrow_mut().deref_mut()) {
+        let (width, height) = ((*window).borrow().width() as f64, (*window).borrow().height() as f64);
+        let mut input = FrameInput::default();
+        for mut event in (*window).borrow().events().iter() {
+            match event.value {
+                WindowEvent::Key(key, action, _) => input.0.push(RawInput::Key(key, action)),
+                WindowEvent::MouseButton(button, action, _) => input.0.push(RawInput::Mouse(button, action)),
+                WindowEvent::CursorPos(x, y, _) => input.0.push(RawInput::Cursor(x / width * 2.0 - 1.0, y / height * 2.0 - 1.0)),
+                WindowEvent::Scroll(_, steps, _) => input.0.push(RawInput::Scroll(steps)),
+                _ => {}
+            }
+            // The mouse flies the ship, so keep the camera from reacting to it too.
+            event.inhibited = event.value.is_mouse_event();
+        }
+
+        run_frame(system, &mut timestep, &mut last_frame, input, workers);
+    }
+}
+
+//
+// #[derive(Clone)]
+// struct ExpandingSphere {
+//     // sn: SceneNode,
+//     time: Instant
+// }
+//
+// #[derive(Clone)]
+// struct ScanPulses {
+//     pulses: Vec<ExpandingSphere>,
+//     parent_sn: SceneNode
+// }
+//
+// #[derive(Clone)]
+// struct ExpandingSphereEffect(Point3<f64>);
+//
+// fn init_scan_pulse_visualizer(system: &mut System, mut sn: SceneNode) {
+//
+//     system.build_a ctor(ScanPulses {
+//         pulses: Vec::new(),
+//         parent_sn: sn
+//     })
+//         .with_handler(move |st, ExpandingSphereEffect(pos), outbox| {
+//             let mut node = st.parent_sn.add_sphere(0.0);
+//             node.set_local_translation(pos.cast().into());
+//             node.set_color(0.0, 1.0, 1.0);
+//
+//             st.pulses.push(ExpandingSphere {
+//                 sn: node,
+//                 time: Instant::now()
+//             })
+//         })
+//         .with_handler(move |st, _: &Tick, outbox| {
+//             st.pulses.retain_mut(|es| {
+//                 let age = Instant::now().duration_since(es.time);
+//
+//                 let age_t = age.as_secs_f64();
+//
+//                 if age_t >= SCAN_PING_RANGE / SCAN_PING_SPEED {
+//                     es.sn.unlink();
+//                     false
+//                 } else {
+//                     es.sn.set_local_scale(
+//                         (age_t * SCAN_PING_SPEED) as f32,
+//                         (age_t * SCAN_PING_SPEED) as f32,
+//                         (age_t * SCAN_PING_SPEED) as f32
+//                     );
+//                     true
+//                 }
+//             });
+//         });
+// }
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+    use std::rc::Rc;
+    use std::sync::mpsc::Receiver;
+    use std::time::Duration;
+
+    use nalgebra::Vector3;
+
+    use voyagers_core::actors::{ActorBuilder, System};
+    use voyagers_core::actors::Fate::Keep;
+    use voyagers_core::clock::{FixedTimestep, ManualClock, Tick};
+    use voyagers_core::testing::{collect, test_system, Ticks};
+
+    use crate::flight::{Steer, Throttle};
+    use crate::game::{ShipId, TIMESTEP};
+    use crate::input::{FrameInput, Pitch, RawInput, ToggleMouseSteering};
+    use crate::render::{create_fighter_controller, run_frame};
+
+    #[test]
+    fn ticks_arrive_without_input() {
+        let mut system = System::new();
+
+        let clock = ManualClock::new();
+        system.set_clock(clock.clone());
+        let mut timestep = FixedTimestep::new(TIMESTEP);
+        let mut last_frame = system.input_interface.now();
+
+        let ticks = Rc::new(Cell::new(0));
+        let counted = ticks.clone();
+        system.create_actor(ActorBuilder::new(()).with_handler(move |_, _: &Tick, _| {
+            counted.set(counted.get() + 1);
+            Keep
+        }).build());
+
+        // Two ticks worth of time per frame, with the player keeping their hands off.
+        for _ in 0..10 {
+            clock.advance(TIMESTEP * 2 + Duration::from_micros(1));
+            run_frame(&mut system, &mut timestep, &mut last_frame, FrameInput::default(), 1);
+        }
+
+        assert_eq!(ticks.get(), 20);
+    }
+
+    /// A fighter controller, with the Steer and Throttle it sends the flight model every Tick.
+    fn fighter_controller() -> (System, Ticks, Receiver<Vector3<f64>>, Receiver<f64>) {
+        let (mut system, ticks) = test_system(TIMESTEP);
+
+        create_fighter_controller(&mut system, ShipId(1));
+        let steer = collect(&mut system, |Steer(_, steer), _| *steer);
+        let throttle = collect(&mut system, |Throttle(_, throttle), _| *throttle);
+
+        (system, ticks, steer, throttle)
+    }
+
+    /// What the controller sends on the next Tick.
+    fn next<T>(system: &mut System, ticks: &mut Ticks, sent: &Receiver<T>) -> T {
+        ticks.run(system, 1);
+        sent.try_iter().last().unwrap()
+    }
+
+    #[test]
+    fn the_cursor_steers_outside_the_dead_zone() {
+        let (mut system, mut ticks, steer, _) = fighter_controller();
+        system.send(ToggleMouseSteering);
+
+        system.send(RawInput::Cursor(0.03, -0.04));
+        assert_eq!(next(&mut system, &mut ticks, &steer), Vector3::zeros());
+
+        // Right of and above the centre: yaw right and pitch up.
+        system.send(RawInput::Cursor(0.5, -0.25));
+        assert_eq!(next(&mut system, &mut ticks, &steer), Vector3::new(0.25, -0.5, 0.0));
+
+        system.send(RawInput::Cursor(-2.0, 3.0));
+        assert_eq!(next(&mut system, &mut ticks, &steer), Vector3::new(-1.0, 1.0, 0.0));
+    }
+
+    #[test]
+    fn scrolling_moves_the_throttle() {
+        let (mut system, mut ticks, _, throttle) = fighter_controller();
+        system.send(ToggleMouseSteering);
+
+        system.send(RawInput::Scroll(3.0));
+        assert!((next(&mut system, &mut ticks, &throttle) - 0.15).abs() < 1e-9);
+
+        system.send(RawInput::Scroll(-1.0));
+        assert!((next(&mut system, &mut ticks, &throttle) - 0.1).abs() < 1e-9);
+
+        system.send(RawInput::Scroll(-10.0));
+        assert_eq!(next(&mut system, &mut ticks, &throttle), 0.0);
+    }
+
+    #[test]
+    fn toggling_switches_between_actions_and_the_mouse() {
+        let (mut system, mut ticks, steer, throttle) = fighter_controller();
+
+        // Steered by actions to begin with, which leave the mouse alone.
+        system.send(Pitch(1.0));
+        system.send(RawInput::Cursor(0.5, 0.0));
+        system.send(RawInput::Scroll(3.0));
+        assert_eq!(next(&mut system, &mut ticks, &steer), Vector3::new(1.0, 0.0, 0.0));
+        assert_eq!(throttle.try_iter().last(), Some(0.0));
+
+        // Switching stops the turn the actions left behind.
+        system.send(ToggleMouseSteering);
+        assert_eq!(next(&mut system, &mut ticks, &steer), Vector3::zeros());
+
+        system.send(RawInput::Cursor(0.5, 0.0));
+        assert_eq!(next(&mut system, &mut ticks, &steer), Vector3::new(0.0, -0.5, 0.0));
+
+        system.send(ToggleMouseSteering);
+        assert_eq!(next(&mut system, &mut ticks, &steer), Vector3::zeros());
+
+        system.send(Pitch(-1.0));
+        assert_eq!(next(&mut system, &mut ticks, &steer), Vector3::new(-1.0, 0.0, 0.0));
+    }
+}